@@ -1,45 +1,61 @@
-use dialoguer::{Select, theme::ColorfulTheme};
-use clap::ArgMatches;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use wpe::*;
+use crate::output::{Output, Kind};
+use crate::cli::{AccountsArgs, AccountCommand};
+use crate::session::{Session, Screen};
+
+/// The account a command acts on. Without `--account` one is picked from a list,
+/// which headless mode has no way to do.
+///
+/// # Arguments
+///
+/// * `api` - &API
+/// * `account` - Option<&str>
+/// * `headless` - bool
+/// * `action` - what the account is needed for, e.g. "add a site"
+pub fn require(api: &API, account: Option<&str>, headless: bool, action: &str) -> Result<String> {
+    if let Some(id) = account {
+        return Ok(id.to_string());
+    }
+    if headless {
+        return Err(CliError::new(ErrorKind::Usage, format!("--account is required to {}.", action)).into());
+    }
+
+    Picker::paged("Select an account", "name", |page| api.get_accounts(Some(page)))
+        .interact()?
+        .and_then(|a| a["id"].as_str().map(String::from))
+        .ok_or_else(|| anyhow!("No account selected."))
+}
 
 /// Handles logic for the accounts command.
 ///
 /// # Arguments
 ///
-/// * `sub_n` - &ArgMatches
-/// * `command` - API
-/// * `headless` - Option<&bool>
-pub fn init(sub_n: &ArgMatches, command: API, headless: Option<&bool>) -> Result<()> {
-    let page = sub_n.get_one::<String>("PAGE");
-    let page_num: u8;
-    // Check for provided page argument, else provide default.
-    match page {
-        Some(x) => {
-            page_num = x.parse::<u8>().unwrap();
+/// * `args` - &AccountsArgs
+/// * `command` - &API
+/// * `headless` - bool
+/// * `out` - &Output
+pub fn init(args: &AccountsArgs, command: &API, headless: bool, out: &Output) -> Result<()> {
+    match &args.command {
+        Some(AccountCommand::Get { account }) => {
+            let id = command.resolve(Resource::Account, account)?;
+            let res = command.get_account_by_id(&id)?;
+            out.print(&res, Kind::Accounts)?;
         },
+        Some(AccountCommand::List) => list(command, args.page, out)?,
+        None if headless => list(command, args.page, out)?,
         None => {
-            page_num = 0;
+            Session::new(command, out, None)
+                .force_production(args.force_production)
+                .run(Screen::Accounts)?;
         }
-
-    }
-    // Fetch sites and display results. Will also show paginated results.
-    let next = command.get_accounts(Some(page_num)).unwrap();
-    let results = next["results"].as_array().unwrap();
-
-    if let Some(true) = headless {
-        let r = serde_json::to_string_pretty(results)?;
-        println!("{}", &r);
-    } else {
-        let selection = get_selections!(results, "Select a site to view...", "name");
-
-        let item = &results[selection]["id"];
-        let account = command.get_account_by_id(
-            &item.as_str().unwrap()
-            ).unwrap();
-
-        println!("Selection: {}", serde_json::to_string_pretty(&account)?);
     }
 
     Ok(())
 }
+
+/// Fetch accounts and display results. Will also show paginated results.
+fn list(command: &API, page: u32, out: &Output) -> Result<()> {
+    let next = command.get_accounts(Some(page))?;
+    out.print(&next["results"], Kind::Accounts)
+}
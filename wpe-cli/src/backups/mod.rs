@@ -0,0 +1,72 @@
+use anyhow::{anyhow, Result};
+use wpe::*;
+use crate::output::{Output, Kind};
+use crate::cli::{BackupsArgs, BackupCommand};
+
+/// Provides logic for the backup command.
+///
+/// # Arguments
+///
+/// * `args` - &BackupsArgs
+/// * `api` - &API
+/// * `out` - &Output
+pub fn init(args: &BackupsArgs, api: &API, out: &Output) -> Result<()> {
+    match &args.command {
+        BackupCommand::Create { install, description, emails } => {
+            let install_id = api.resolve(Resource::Install, install)?;
+
+            let data = Backup {
+                description: description.to_string(),
+                notification_emails: emails.clone()
+            };
+
+            // The backup is recorded in the ledger by the API call itself.
+            let backup = api.backup(&install_id, &data)?;
+
+            out.print(&backup, Kind::Other)?;
+        },
+        BackupCommand::List { install } => {
+            let install_id = install
+                .as_ref()
+                .map(|i| api.resolve(Resource::Install, i))
+                .transpose()?;
+            let ledger = BackupLedger::load()?;
+
+            out.print(&ledger.list(install_id.as_deref()), Kind::Backups)?;
+        },
+        BackupCommand::Refresh => {
+            let mut ledger = BackupLedger::load()?;
+
+            // Only re-poll backups that haven't reached a final status. A failed one keeps
+            // its status and the error, so the rest still refresh and get saved.
+            let mut failed = 0;
+            for entry in ledger.backups.iter_mut().filter(|b| b.is_pending()) {
+                match api.get_backup(&entry.install_id, &entry.id) {
+                    Ok(backup) => {
+                        if let Some(status) = backup["status"].as_str() {
+                            entry.status = status.to_string();
+                        }
+                        entry.error = None;
+                    },
+                    Err(err) if CliError::kind_of(&err) == ErrorKind::NotFound => {
+                        entry.status = "not_found".to_string();
+                        entry.error = None;
+                    },
+                    Err(err) => {
+                        entry.error = Some(format!("{:#}", err));
+                        failed += 1;
+                    }
+                }
+            }
+            ledger.save()?;
+
+            out.print(&ledger.list(None), Kind::Backups)?;
+
+            if failed > 0 {
+                return Err(anyhow!("{} backup(s) couldn't be refreshed, see the error of each.", failed));
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use dialoguer::{
+    Select,
+    MultiSelect,
+    Input,
+    Confirm,
+    theme::ColorfulTheme
+};
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use wpe::*;
+use crate::output::{Output, Kind};
+use crate::filter::Criteria;
+use crate::guard::{self, Guard, Impact};
+use crate::cli::{CacheType, Environment};
+
+/// Actions that can be applied to several installs at once.
+enum Action {
+    PurgeCache(String),
+    Backup(Backup),
+    ChangeEnvironment(String),
+    Delete,
+    GrantAccess(String)
+}
+
+impl Action {
+    fn describe(&self) -> String {
+        match self {
+            Action::PurgeCache(kind) => format!("Purge {} cache", kind),
+            Action::Backup(backup) => format!("Back up (\"{}\")", backup.description),
+            Action::ChangeEnvironment(env) => format!("Change environment to {}", env),
+            Action::Delete => "Delete".to_string(),
+            Action::GrantAccess(email) => format!("Grant {} access", email)
+        }
+    }
+}
+
+/// Ask which action to run and collect whatever it needs. `None` means back.
+fn choose_action() -> Result<Option<Action>> {
+    let options = [
+        "Purge cache",
+        "Back up",
+        "Change environment",
+        "Delete",
+        "Grant user access",
+        "« Back"
+    ];
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Choose an action for the selected installs")
+        .items(&options)
+        .default(0)
+        .interact()?;
+
+    let action = match selection {
+        0 => {
+            let caches = CacheType::labels();
+            let kind = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Select a cache type")
+                .items(&caches)
+                .default(0)
+                .interact()?;
+            Action::PurgeCache(caches[kind].to_string())
+        },
+        1 => {
+            let description: String = Input::new()
+                .with_prompt("Enter a backup description")
+                .interact()?;
+            let emails: String = Input::new()
+                .with_prompt("Notification emails, comma separated")
+                .allow_empty(true)
+                .interact()?;
+            Action::Backup(Backup {
+                description,
+                notification_emails: emails
+                    .split(',')
+                    .map(|e| e.trim().to_string())
+                    .filter(|e| !e.is_empty())
+                    .collect()
+            })
+        },
+        2 => {
+            let environments = Environment::labels();
+            let env = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Select an environment")
+                .items(&environments)
+                .default(0)
+                .interact()?;
+            Action::ChangeEnvironment(environments[env].to_string())
+        },
+        3 => Action::Delete,
+        4 => {
+            let email: String = Input::new()
+                .with_prompt("Enter the user's email")
+                .interact()?;
+            Action::GrantAccess(email)
+        },
+        _ => return Ok(None)
+    };
+
+    Ok(Some(action))
+}
+
+/// Give an account user access to an install, keeping the installs they already have.
+fn grant_access(api: &API, install: &Value, email: &str) -> Result<Value> {
+    let account_id = install["account"]["id"]
+        .as_str()
+        .ok_or_else(|| anyhow!("install has no account"))?;
+
+    let users = api.get_all_account_users(account_id)?;
+    let user = users
+        .iter()
+        .find(|u| u["email"].as_str().is_some_and(|e| e.eq_ignore_ascii_case(email)))
+        .ok_or_else(|| anyhow!("{} is not a user of account {}", email, account_id))?;
+
+    let mut install_ids: Vec<String> = user["installs"]
+        .as_array()
+        .map(|i| i.iter().filter_map(|i| i["id"].as_str().map(String::from)).collect())
+        .unwrap_or_default();
+    let install_id = install["id"]
+        .as_str()
+        .ok_or_else(|| anyhow!("install has no ID"))?
+        .to_string();
+    if !install_ids.contains(&install_id) {
+        install_ids.push(install_id);
+    }
+
+    let data = AccountUserPatch {
+        user: UserPatch {
+            roles: None,
+            install_ids: Some(install_ids)
+        }
+    };
+
+    api.update_user(account_id, user["user_id"].as_str().unwrap_or_default(), &data)
+}
+
+fn apply(api: &API, install: &Value, action: &Action) -> Result<Value> {
+    let id = install["id"].as_str().ok_or_else(|| anyhow!("install has no ID"))?;
+
+    match action {
+        Action::PurgeCache(kind) => api.purge_cache(id, kind.to_string()),
+        Action::Backup(backup) => api.backup(id, backup),
+        Action::ChangeEnvironment(env) => api.update_install(id, &InstallPatch {
+            environment: Some(env.to_string()),
+            ..InstallPatch::default()
+        }),
+        Action::Delete => api.delete_install(id),
+        Action::GrantAccess(email) => grant_access(api, install, email)
+    }
+}
+
+/// Tick several installs and apply one action to all of them.
+///
+/// # Arguments
+///
+/// * `api` - &API
+/// * `criteria` - &Criteria
+/// * `account` - Option<&str>
+/// * `force_production` - bool
+/// * `out` - &Output
+pub fn init(api: &API, criteria: &Criteria, account: Option<&str>, force_production: bool, out: &Output) -> Result<()> {
+    let installs = criteria.apply(api.get_all_installs(account)?);
+    if installs.is_empty() {
+        return Err(anyhow!("No installs match the given filters."));
+    }
+
+    let labels: Vec<String> = installs
+        .iter()
+        .map(|i| format!(
+            "{} ({})",
+            i["name"].as_str().unwrap_or_default(),
+            i["environment"].as_str().unwrap_or_default()
+        ))
+        .collect();
+
+    let selected = MultiSelect::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select installs (space to toggle, enter to confirm)")
+        .items(&labels)
+        .interact()?;
+
+    if selected.is_empty() {
+        println!("No installs selected.");
+        return Ok(());
+    }
+
+    let Some(action) = choose_action()? else { return Ok(()) };
+
+    // Show exactly what is about to happen before touching anything.
+    println!("Action: {}", action.describe());
+    println!("Installs ({}):", selected.len());
+    for i in &selected {
+        println!("  - {}", labels[*i]);
+    }
+    // Each delete goes through the guard instead, so production installs need --force-production
+    // and every name is typed back. A refused one is skipped, not the whole batch.
+    let guard = Guard { yes: false, force_production, headless: false };
+    let mut refused = HashMap::new();
+    if !matches!(action, Action::Delete) {
+        if !Confirm::new().with_prompt("Continue?").default(false).interact()? {
+            println!("Cancelling.");
+            return Ok(());
+        }
+    } else {
+        for i in &selected {
+            let id = installs[*i]["id"].as_str().ok_or_else(|| anyhow!("install has no ID"))?;
+            if let Err(err) = guard::confirm(&Impact::install(api, id)?, &guard) {
+                if CliError::kind_of(&err) != ErrorKind::Usage {
+                    return Err(err);
+                }
+                eprintln!("{}", err);
+                refused.insert(*i, err.to_string());
+            }
+        }
+    }
+
+    // Keep going on failure so one bad install doesn't stop the rest.
+    let report: Vec<Value> = selected
+        .iter()
+        .map(|i| {
+            let install = &installs[*i];
+            let (result, detail) = match refused.get(i) {
+                Some(reason) => ("skipped", reason.clone()),
+                None => match apply(api, install, &action) {
+                    Ok(_) => ("ok", String::new()),
+                    Err(e) => ("failed", e.to_string())
+                }
+            };
+            json!({
+                "install": install["name"],
+                "id": install["id"],
+                "result": result,
+                "detail": detail
+            })
+        })
+        .collect();
+
+    out.print(&report, Kind::Other)
+}
@@ -0,0 +1,24 @@
+use anyhow::Result;
+use wpe::*;
+use crate::output::{Output, Kind};
+use crate::cli::{CacheArgs, CacheCommand};
+
+/// Provides logic for the cache command.
+///
+/// # Arguments
+///
+/// * `args` - &CacheArgs
+/// * `api` - &API
+/// * `out` - &Output
+pub fn init(args: &CacheArgs, api: &API, out: &Output) -> Result<()> {
+    match &args.command {
+        CacheCommand::Purge { install, cache } => {
+            let install_id = api.resolve(Resource::Install, install)?;
+            let res = api.purge_cache(&install_id, cache.as_str().to_string())?;
+
+            out.print(&res, Kind::Other)?;
+        }
+    }
+
+    Ok(())
+}
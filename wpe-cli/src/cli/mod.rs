@@ -0,0 +1,646 @@
+use std::io::IsTerminal;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use crate::filter::Criteria;
+use crate::output::Format;
+
+/// Every command follows `wpe <resource> <verb> [--flags]`.
+#[derive(Parser, Debug)]
+#[command(
+    name = "wpe",
+    about = "WPEngine CLI",
+    arg_required_else_help = true,
+    after_help = "Exit codes: 0 success, 1 other error, 2 usage, 3 authentication, 4 not found, 5 validation, \
+                  6 rate limited, 7 API error, 8 network error."
+)]
+pub struct Cli {
+    #[command(flatten)]
+    pub global: Global,
+    #[command(subcommand)]
+    pub command: Commands
+}
+
+/// Flags accepted by every command.
+#[derive(Args, Debug)]
+pub struct Global {
+    /// Enables headless mode, implied when stdin or stdout isn't a terminal
+    #[arg(short = 'H', long, global = true)]
+    pub headless: bool,
+    /// Print the requests that would change anything to stderr instead of sending them, reads are still sent
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+    /// Prompt even when stdin or stdout isn't a terminal
+    #[arg(long, global = true, conflicts_with = "headless")]
+    pub interactive: bool,
+    /// Output format, defaults to table on a terminal and json when piped
+    #[arg(long, value_name = "FORMAT", global = true)]
+    pub output: Option<Format>,
+    /// Account ID or name that listings are scoped to and new sites and users are created in
+    #[arg(long, global = true)]
+    pub account: Option<String>,
+    /// Comma separated fields to keep, e.g. name,id,account.id
+    #[arg(long, value_delimiter = ',', global = true)]
+    pub fields: Vec<String>,
+    /// JSONPath or jq-style expression applied to the response, e.g. '.results[].name'
+    #[arg(long, value_name = "EXPRESSION", global = true)]
+    pub query: Option<String>
+}
+
+/// Confirmation flags for deletes that can take production installs with them.
+#[derive(Args, Debug)]
+pub struct Confirmation {
+    /// Skip the confirmation, required in headless mode
+    #[arg(short, long)]
+    pub yes: bool,
+    /// Allow deleting production installs
+    #[arg(long)]
+    pub force_production: bool
+}
+
+/// A request body file, taken in place of the flags of an add or update.
+#[derive(Args, Debug)]
+pub struct BodyFile {
+    /// JSON or YAML request body, or an array of them, read from a file or stdin with '-'
+    #[arg(short = 'f', long = "file", value_name = "PATH")]
+    pub file: Option<String>
+}
+
+impl Global {
+    /// Whether prompts are off. Without a terminal to prompt on, e.g. in CI,
+    /// headless mode is used unless `--interactive` asks otherwise.
+    pub fn headless(&self) -> bool {
+        if self.interactive {
+            return false;
+        }
+        self.headless || !(std::io::stdin().is_terminal() && std::io::stdout().is_terminal())
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Browse sites, or manage them with a verb.
+    #[command(after_help = "Without a verb, sites are shown as a selection and picking one displays more options.")]
+    Sites(SitesArgs),
+    /// Browse installs, or manage them with a verb.
+    #[command(after_help = "Without a verb, installs are shown as a selection and picking one displays more options.")]
+    Installs(InstallsArgs),
+    /// Browse or look up accounts.
+    Accounts(AccountsArgs),
+    /// Manage the users of an account, set with --account.
+    Users(UsersArgs),
+    /// Manage the domains of an install.
+    Domains(DomainsArgs),
+    /// Manage the SSH keys of the authenticated user.
+    #[command(name = "ssh-keys")]
+    SshKeys(SshKeysArgs),
+    /// Create and track install backups.
+    #[command(
+        alias = "backup",
+        after_help = "Backups created through wpe are recorded in $HOME/.config/wpe/backups.json."
+    )]
+    Backups(BackupsArgs),
+    /// Purge the caches of an install.
+    Cache(CacheArgs),
+    /// Create sites and installs from a CSV, YAML or JSON file.
+    #[command(after_help = "Each row has a site, account, install and environment, e.g. the CSV header \
+        `site,account,install,environment`. Rows of the same site and account share it, a site that exists already \
+        gets the new installs, and a row without an install only creates the site. Every row is checked before \
+        anything is created. Rows that succeeded are recorded in the results file and skipped when it is run again.")]
+    Import {
+        /// CSV, YAML or JSON file of rows
+        file: String,
+        /// Where the outcome of each row is written [default: <FILE>.results.json]
+        #[arg(long, value_name = "PATH")]
+        results: Option<String>,
+        /// Sites created at the same time
+        #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u8).range(1..=16))]
+        concurrency: u8,
+        /// Skip the confirmation
+        #[arg(short, long)]
+        yes: bool
+    },
+    /// Show the changes that bring WP Engine in line with a manifest.
+    #[command(after_help = "The manifest lists accounts with their sites, installs, domains and users. \
+        A list that is left out isn't managed, an empty one means there should be none. \
+        Resources missing from a managed list are only deleted with --prune.")]
+    Plan {
+        /// YAML or JSON manifest, or '-' for stdin
+        #[arg(short = 'f', long, value_name = "PATH")]
+        file: String,
+        /// Include deletes of resources missing from the manifest
+        #[arg(long)]
+        prune: bool
+    },
+    /// Apply the changes of a plan in dependency order.
+    Apply {
+        /// YAML or JSON manifest, or '-' for stdin
+        #[arg(short = 'f', long, value_name = "PATH")]
+        file: String,
+        /// Delete resources missing from the manifest
+        #[arg(long)]
+        prune: bool,
+        #[command(flatten)]
+        confirm: Confirmation
+    },
+    /// Export every account, site, install, domain, user and ssh key as one snapshot.
+    #[command(after_help = "The snapshot is versioned and timestamped, and written as YAML when the file ends in \
+        .yaml or .yml. Use --account to export a single account, ssh keys belong to the authenticated user.")]
+    Export {
+        /// File to write the snapshot to, it is printed when left out
+        #[arg(short = 'o', long, value_name = "PATH")]
+        out: Option<String>
+    },
+    /// Edit a resource in $EDITOR and send the changed fields.
+    #[command(after_help = "The editor is taken from $WPE_EDITOR, $VISUAL or $EDITOR, and falls back to vi. \
+        Saving without changes or emptying the file cancels the edit.")]
+    Edit {
+        #[command(subcommand)]
+        command: EditCommand
+    },
+    /// Authenticate with WP Engine API
+    Auth {
+        #[command(subcommand)]
+        command: AuthCommand
+    },
+    /// Get API status
+    Status,
+    /// Print a shell completion script.
+    #[command(after_help = "e.g. `source <(wpe completions bash)`. Bash, zsh and fish also complete resource names from the local index.")]
+    Completions {
+        /// Shell to generate completions for
+        shell: Shell
+    },
+    /// Generate the command reference from this binary.
+    Docs {
+        #[command(subcommand)]
+        command: DocsCommand
+    },
+    /// Complete a resource name from the local index.
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        #[arg(num_args = 0.., allow_hyphen_values = true, trailing_var_arg = true)]
+        words: Vec<String>
+    },
+    /// Show accounts, sites, installs and domains as a tree.
+    #[command(after_help = "Use --output json or yaml for the nested structure.")]
+    Tree {
+        /// Levels to show: 1 accounts, 2 sites, 3 installs, 4 domains
+        #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u8).range(1..=4))]
+        depth: u8
+    },
+    /// Start an interactive shell that keeps a current account, site and install.
+    #[command(after_help = "Type `help` inside the shell for its own commands.")]
+    Shell,
+    /// Open a full-screen dashboard of accounts, sites and installs.
+    Tui {
+        /// Seconds between background refreshes, at least 5
+        #[arg(long, value_name = "SECS", default_value_t = 60, value_parser = clap::value_parser!(u64).range(5..))]
+        interval: u64
+    },
+    /// Get API swagger
+    Swagger
+}
+
+#[derive(Subcommand, Debug)]
+pub enum EditCommand {
+    /// Edit a site's name.
+    Site {
+        /// Site ID or name
+        site: String
+    },
+    /// Edit an install's site and environment.
+    Install {
+        /// Install ID or name
+        install: String
+    },
+    /// Edit whether a domain is primary and where it redirects.
+    Domain {
+        /// Install ID or name the domains belong to
+        #[arg(long)]
+        install: String,
+        /// Domain ID or name
+        domain: String
+    },
+    /// Edit the roles and installs of a user of the account, set with --account.
+    User {
+        /// User ID or email
+        user: String
+    }
+}
+
+/// Install environments, from the least critical so menus default to development.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Environment {
+    Development,
+    Staging,
+    Production
+}
+
+impl Environment {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Environment::Production => "production",
+            Environment::Staging => "staging",
+            Environment::Development => "development"
+        }
+    }
+
+    /// Every environment, as the menus list them.
+    pub fn labels() -> Vec<&'static str> {
+        Self::value_variants().iter().map(Self::as_str).collect()
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct SitesArgs {
+    #[command(subcommand)]
+    pub command: Option<SiteCommand>,
+    /// The page number of a listing
+    #[arg(long, default_value_t = 0, global = true)]
+    pub page: u32,
+    #[command(flatten)]
+    pub criteria: Criteria,
+    /// Allow the menus to delete production installs
+    #[arg(long)]
+    pub force_production: bool
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SiteCommand {
+    /// List sites.
+    List,
+    /// Show a site.
+    Get {
+        /// Site ID or name
+        site: String
+    },
+    /// Add a site to the account given with --account.
+    Add {
+        /// Site name
+        #[arg(long, required_unless_present = "file", conflicts_with = "file")]
+        name: Option<String>,
+        #[command(flatten)]
+        body: BodyFile
+    },
+    /// Update a site, only the given fields are changed.
+    Update {
+        /// Site ID or name, bodies in a file can give their own "id"
+        #[arg(required_unless_present = "file")]
+        site: Option<String>,
+        /// New site name
+        #[arg(long, conflicts_with = "file")]
+        name: Option<String>,
+        #[command(flatten)]
+        body: BodyFile
+    },
+    /// Delete a site and every install under it.
+    Delete {
+        /// Site ID or name
+        site: String,
+        #[command(flatten)]
+        confirm: Confirmation
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InstallsArgs {
+    #[command(subcommand)]
+    pub command: Option<InstallCommand>,
+    /// The page number of a listing
+    #[arg(long, default_value_t = 0, global = true)]
+    pub page: u32,
+    #[command(flatten)]
+    pub criteria: Criteria,
+    /// Allow the menus to delete production installs
+    #[arg(long)]
+    pub force_production: bool
+}
+
+#[derive(Subcommand, Debug)]
+pub enum InstallCommand {
+    /// List installs.
+    List,
+    /// Show an install.
+    Get {
+        /// Install ID or name
+        install: String
+    },
+    /// Add an install to a site.
+    Add {
+        /// Install name, also used for its wpengine.com subdomain
+        #[arg(long, required_unless_present = "file", conflicts_with = "file")]
+        name: Option<String>,
+        /// Site ID or name the install belongs to
+        #[arg(long, required_unless_present = "file", conflicts_with = "file")]
+        site: Option<String>,
+        /// Environment of the install
+        #[arg(long, required_unless_present = "file", conflicts_with = "file")]
+        environment: Option<Environment>,
+        #[command(flatten)]
+        body: BodyFile
+    },
+    /// Update an install, only the given fields are changed.
+    Update {
+        /// Install ID or name, bodies in a file can give their own "id"
+        #[arg(required_unless_present = "file")]
+        install: Option<String>,
+        /// Site ID or name to move the install to
+        #[arg(long, conflicts_with = "file")]
+        site: Option<String>,
+        /// New environment
+        #[arg(long, conflicts_with = "file")]
+        environment: Option<Environment>,
+        #[command(flatten)]
+        body: BodyFile
+    },
+    /// Delete an install.
+    Delete {
+        /// Install ID or name
+        install: String,
+        #[command(flatten)]
+        confirm: Confirmation
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct AccountsArgs {
+    #[command(subcommand)]
+    pub command: Option<AccountCommand>,
+    /// The page number of a listing
+    #[arg(long, default_value_t = 0, global = true)]
+    pub page: u32,
+    /// Allow the menus to delete production installs
+    #[arg(long)]
+    pub force_production: bool
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AccountCommand {
+    /// List accounts.
+    List,
+    /// Show an account.
+    Get {
+        /// Account ID or name
+        account: String
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct UsersArgs {
+    #[command(subcommand)]
+    pub command: UserCommand
+}
+
+#[derive(Subcommand, Debug)]
+pub enum UserCommand {
+    /// List the users of the account.
+    List {
+        /// The page number
+        #[arg(long, default_value_t = 0)]
+        page: u32
+    },
+    /// Show a user.
+    Get {
+        /// User ID or email
+        user: String
+    },
+    /// Invite a user to the account.
+    Add {
+        /// Email address the invite is sent to
+        #[arg(long, required_unless_present = "file", conflicts_with = "file")]
+        email: Option<String>,
+        /// First name
+        #[arg(long, required_unless_present = "file", conflicts_with = "file")]
+        first_name: Option<String>,
+        /// Last name
+        #[arg(long, required_unless_present = "file", conflicts_with = "file")]
+        last_name: Option<String>,
+        /// Roles, e.g. owner, full, full,billing, partial or partial,billing
+        #[arg(long, required_unless_present = "file", conflicts_with = "file")]
+        roles: Option<String>,
+        /// Install ID or name a partial user can access, repeat for more
+        #[arg(long = "install", value_name = "INSTALL", conflicts_with = "file")]
+        installs: Vec<String>,
+        #[command(flatten)]
+        body: BodyFile
+    },
+    /// Update a user, only the given fields are changed.
+    Update {
+        /// User ID or email, bodies in a file can give their own "id"
+        #[arg(required_unless_present = "file")]
+        user: Option<String>,
+        /// New roles, e.g. owner, full, full,billing, partial or partial,billing
+        #[arg(long, conflicts_with = "file")]
+        roles: Option<String>,
+        /// Install ID or name a partial user can access, repeat for more
+        #[arg(long = "install", value_name = "INSTALL", conflicts_with = "file")]
+        installs: Vec<String>,
+        #[command(flatten)]
+        body: BodyFile
+    },
+    /// Remove a user from the account.
+    Delete {
+        /// User ID or email
+        user: String,
+        /// Skip the confirmation, required in headless mode
+        #[arg(short, long)]
+        yes: bool
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct DomainsArgs {
+    #[command(subcommand)]
+    pub command: DomainCommand
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DomainCommand {
+    /// List the domains of an install.
+    List {
+        /// Install ID or name the domains belong to
+        #[arg(long)]
+        install: String,
+        /// The page number
+        #[arg(long, default_value_t = 0)]
+        page: u32
+    },
+    /// Show a domain.
+    Get {
+        /// Install ID or name the domains belong to
+        #[arg(long)]
+        install: String,
+        /// Domain ID or name
+        domain: String
+    },
+    /// Add a domain to an install.
+    Add {
+        /// Install ID or name the domains belong to
+        #[arg(long)]
+        install: String,
+        /// Domain name, e.g. www.example.com
+        #[arg(long, required_unless_present = "file", conflicts_with = "file")]
+        name: Option<String>,
+        /// Make it the primary domain
+        #[arg(long, conflicts_with = "file")]
+        primary: bool,
+        #[command(flatten)]
+        body: BodyFile
+    },
+    /// Update a domain, only the given fields are changed.
+    Update {
+        /// Install ID or name the domains belong to
+        #[arg(long)]
+        install: String,
+        /// Domain ID or name, bodies in a file can give their own "id"
+        #[arg(required_unless_present = "file")]
+        domain: Option<String>,
+        /// Whether it is the primary domain
+        #[arg(long, conflicts_with = "file")]
+        primary: Option<bool>,
+        /// Domain ID to redirect to
+        #[arg(long, conflicts_with = "file")]
+        redirect_to: Option<String>,
+        #[command(flatten)]
+        body: BodyFile
+    },
+    /// Remove a domain from an install.
+    Delete {
+        /// Install ID or name the domains belong to
+        #[arg(long)]
+        install: String,
+        /// Domain ID or name
+        domain: String,
+        /// Skip the confirmation, required in headless mode
+        #[arg(short, long)]
+        yes: bool
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct SshKeysArgs {
+    #[command(subcommand)]
+    pub command: SshKeyCommand
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SshKeyCommand {
+    /// List SSH keys.
+    List {
+        /// The page number
+        #[arg(long, default_value_t = 0)]
+        page: u32
+    },
+    /// Add an SSH key.
+    Add {
+        /// Public key, e.g. the contents of ~/.ssh/id_ed25519.pub
+        #[arg(long, required_unless_present = "file", conflicts_with = "file")]
+        public_key: Option<String>,
+        #[command(flatten)]
+        body: BodyFile
+    },
+    /// Delete an SSH key.
+    Delete {
+        /// SSH key ID
+        key: String,
+        /// Skip the confirmation, required in headless mode
+        #[arg(short, long)]
+        yes: bool
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct BackupsArgs {
+    #[command(subcommand)]
+    pub command: BackupCommand
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BackupCommand {
+    /// Request a backup of an install.
+    Create {
+        /// Install ID or name to back up
+        #[arg(long)]
+        install: String,
+        /// Backup description
+        #[arg(long)]
+        description: String,
+        /// Notification email, repeat for more
+        #[arg(long = "email")]
+        emails: Vec<String>
+    },
+    /// List recorded backups, newest first.
+    List {
+        /// Install ID or name to show backups for
+        #[arg(long)]
+        install: Option<String>
+    },
+    /// Re-poll the status of pending backups.
+    Refresh
+}
+
+/// Caches that can be purged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum CacheType {
+    Object,
+    Page,
+    Cdn
+}
+
+impl CacheType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheType::Object => "object",
+            CacheType::Page => "page",
+            CacheType::Cdn => "cdn"
+        }
+    }
+
+    /// Every cache, as the menus list them.
+    pub fn labels() -> Vec<&'static str> {
+        Self::value_variants().iter().map(Self::as_str).collect()
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    pub command: CacheCommand
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheCommand {
+    /// Purge a cache of an install.
+    Purge {
+        /// Install ID or name to purge
+        #[arg(long)]
+        install: String,
+        /// Which cache to purge
+        #[arg(long = "type", value_name = "TYPE")]
+        cache: CacheType
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AuthCommand {
+    /// Login to WP Engine API
+    Login,
+    /// Reset authentication
+    Reset
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DocsCommand {
+    /// Write a roff man page for every command.
+    Man {
+        /// Directory to write the pages to
+        #[arg(long, default_value = "man")]
+        dir: String
+    },
+    /// Print a markdown reference of every command.
+    Markdown {
+        /// Write to a file instead of stdout
+        #[arg(long)]
+        out: Option<String>
+    }
+}
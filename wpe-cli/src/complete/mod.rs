@@ -0,0 +1,221 @@
+use clap::{Arg, Command, CommandFactory};
+use clap_complete::Shell;
+use anyhow::Result;
+use wpe::*;
+use crate::output::write_stdout;
+use crate::cli::Cli;
+
+pub const KINDS: [Resource; 3] = [Resource::Account, Resource::Site, Resource::Install];
+
+// Shell glue that asks `wpe __complete` for resource names and falls back to the
+// generated completions wherever a name isn't expected.
+const BASH: &str = r#"
+_wpe_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" line candidates
+    if candidates=$(wpe __complete -- "${COMP_WORDS[@]:1:COMP_CWORD-1}" "$cur" 2>/dev/null); then
+        COMPREPLY=()
+        while IFS= read -r line; do
+            [[ -n "$line" ]] && COMPREPLY+=("$(printf '%q' "$line")")
+        done <<< "$candidates"
+        return 0
+    fi
+    _wpe "$@"
+}
+complete -F _wpe_dynamic -o bashdefault -o default wpe
+"#;
+
+const ZSH: &str = r#"
+_wpe_dynamic() {
+    local -a candidates
+    local output
+    if output=$(wpe __complete -- "${(@)words[2,CURRENT-1]}" "$PREFIX" 2>/dev/null); then
+        candidates=("${(@f)output}")
+        compadd -Q -a candidates
+        return
+    fi
+    _wpe "$@"
+}
+compdef _wpe_dynamic wpe
+"#;
+
+const FISH: &str = r#"
+complete -c wpe -f -n 'wpe __complete -- (commandline -opc)[2..] (commandline -ct) >/dev/null 2>&1' -a '(wpe __complete -- (commandline -opc)[2..] (commandline -ct))'
+"#;
+
+/// The full command tree with global flags copied down to every subcommand.
+pub fn command() -> Command {
+    let mut cmd = Cli::command();
+    cmd.build();
+    cmd
+}
+
+/// Which resource an argument expects, going by its value name, e.g. `--install <INSTALL>`.
+/// Arguments that name a resource must keep their value name to the resource.
+pub fn resource_of(arg: &Arg) -> Option<Resource> {
+    let names = arg.get_value_names()?;
+    KINDS.into_iter().find(|k| names.iter().any(|n| n.eq_ignore_ascii_case(k.label())))
+}
+
+/// Whether a flag anywhere in the command tree takes a value.
+fn takes_value(cmd: &Command, flag: &impl Fn(&Arg) -> bool) -> bool {
+    cmd.get_arguments().any(|a| flag(a) && a.get_action().takes_values())
+        || cmd.get_subcommands().any(|s| takes_value(s, flag))
+}
+
+/// Follow the words of a line down the command tree. Returns the command they
+/// end up in and the indexes of the words that are its positional values.
+pub fn walk<'c>(root: &'c Command, words: &[String]) -> (&'c Command, Vec<usize>) {
+    let mut cmd = root;
+    let mut positionals = Vec::new();
+    let mut skip = false;
+
+    for (i, word) in words.iter().enumerate() {
+        if skip {
+            skip = false;
+        } else if let Some(long) = word.strip_prefix("--") {
+            skip = !long.contains('=') && takes_value(root, &|a: &Arg| a.get_long() == Some(long));
+        } else if let Some(short) = word.strip_prefix('-').filter(|s| !s.is_empty()) {
+            // A value can follow a short flag as the next word, e.g. `-f sites.yaml`.
+            skip = short.len() == 1 && takes_value(root, &|a: &Arg| a.get_short() == short.chars().next());
+        } else if let Some(sub) = cmd.find_subcommand(word).filter(|_| positionals.is_empty()) {
+            cmd = sub;
+        } else {
+            positionals.push(i);
+        }
+    }
+
+    (cmd, positionals)
+}
+
+/// The resource named by the value of a trailing flag like `--install`, looked
+/// up on the command the other words lead to and on the root's global flags.
+pub fn flag_resource(root: &Command, words: &[String]) -> Option<Resource> {
+    let (last, rest) = words.split_last()?;
+    let long = last.strip_prefix("--")?;
+    let (cmd, _) = walk(root, rest);
+
+    cmd.get_arguments()
+        .chain(root.get_arguments())
+        .find(|a| a.get_long() == Some(long))
+        .and_then(resource_of)
+}
+
+/// The kind of resource the word after `words` names, if any.
+pub fn expected(root: &Command, words: &[String]) -> Option<Resource> {
+    if words.last().is_some_and(|w| w.starts_with("--")) {
+        return flag_resource(root, words);
+    }
+
+    let (cmd, given) = walk(root, words);
+    cmd.get_positionals().nth(given.len()).and_then(resource_of)
+}
+
+/// Names and IDs from the cached index that start with `prefix`, ignoring case.
+pub fn candidates(index: &ResourceIndex, kind: Resource, prefix: &str) -> Vec<String> {
+    let prefix = prefix.to_lowercase();
+    let mut found: Vec<String> = Vec::new();
+
+    for entry in index.entries(kind) {
+        for value in [&entry.name, &entry.id] {
+            if value.to_lowercase().starts_with(&prefix) && !found.contains(value) {
+                found.push(value.to_string());
+            }
+        }
+    }
+
+    found
+}
+
+/// Provides logic for the completions command.
+///
+/// # Arguments
+///
+/// * `shell` - Shell
+pub fn init(shell: Shell) -> Result<()> {
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut Cli::command(), "wpe", &mut script);
+    let mut script = String::from_utf8(script)?;
+
+    match shell {
+        Shell::Bash => script.push_str(BASH),
+        Shell::Zsh => script.push_str(ZSH),
+        Shell::Fish => script.push_str(FISH),
+        _ => {}
+    }
+
+    write_stdout(&script)
+}
+
+/// The hidden `__complete` hook used by the completion scripts. Prints the
+/// matching names for the word being completed, or exits with 1 when that
+/// word isn't a resource so the shell falls back to its static completions.
+///
+/// # Arguments
+///
+/// * `words` - &[String]
+pub fn hook(words: &[String]) -> Result<()> {
+    let (partial, words) = match words.split_last() {
+        Some((partial, words)) => (partial.as_str(), words),
+        None => ("", words)
+    };
+
+    let Some(kind) = expected(&command(), words) else {
+        std::process::exit(1);
+    };
+
+    // Completion must stay instant, so only the local index is read.
+    let index = ResourceIndex::load().unwrap_or_default();
+    let mut text = candidates(&index, kind, partial).join("\n");
+    text.push('\n');
+
+    write_stdout(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    fn expects(line: &str) -> Option<Resource> {
+        expected(&command(), &words(line))
+    }
+
+    #[test]
+    fn every_resource_argument_is_completed() {
+        fn check(cmd: &Command) {
+            for arg in cmd.get_arguments() {
+                let help = arg.get_help().map(|h| h.to_string().to_lowercase()).unwrap_or_default();
+                let named = KINDS.into_iter().find(|k| help.starts_with(&format!("{} id or name", k.label())));
+                assert_eq!(
+                    resource_of(arg).map(|k| k.label()),
+                    named.map(|k| k.label()),
+                    "{} {}", cmd.get_name(), arg.get_id()
+                );
+            }
+            cmd.get_subcommands().for_each(check);
+        }
+        check(&command());
+    }
+
+    #[test]
+    fn finds_the_resource_of_the_next_word() {
+        assert_eq!(expects("sites get"), Some(Resource::Site));
+        assert_eq!(expects("installs update"), Some(Resource::Install));
+        assert_eq!(expects("installs update acmeprod --site"), Some(Resource::Site));
+        assert_eq!(expects("users add --install"), Some(Resource::Install));
+        assert_eq!(expects("sites list --account"), Some(Resource::Account));
+        assert_eq!(expects("domains get --install acmeprod"), None);
+        assert_eq!(expects("installs update --environment"), None);
+    }
+
+    #[test]
+    fn skips_flag_values_when_walking() {
+        let root = command();
+        let (cmd, positionals) = walk(&root, &words("--output json installs -f x.yaml update acmeprod"));
+        assert_eq!(cmd.get_name(), "update");
+        assert_eq!(positionals, vec![6]);
+    }
+}
@@ -0,0 +1,181 @@
+use console::style;
+use dialoguer::Confirm;
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Where each patch field is read from on the fetched resource.
+pub const SITE_FIELDS: &[(&str, &str)] = &[("name", "/name")];
+pub const INSTALL_FIELDS: &[(&str, &str)] = &[("site_id", "/site/id"), ("environment", "/environment")];
+pub const DOMAIN_FIELDS: &[(&str, &str)] = &[("primary", "/primary"), ("redirect_to", "/redirects_to/0/id")];
+pub const USER_FIELDS: &[(&str, &str)] = &[("roles", "/roles"), ("install_ids", "/installs")];
+
+/// A field an update changes.
+pub struct Change {
+    pub field: String,
+    pub before: Value,
+    pub after: Value
+}
+
+/// Lists of objects are compared by their IDs, e.g. a user's installs against `install_ids`.
+fn normalize(value: &Value) -> Value {
+    match value.as_array() {
+        Some(items) if !items.is_empty() && items.iter().all(|i| i["id"].is_string()) => {
+            Value::Array(items.iter().map(|i| i["id"].clone()).collect())
+        },
+        _ => value.clone()
+    }
+}
+
+/// Whether a field keeps its value. Lists like a user's installs are compared
+/// as sets, sending the same IDs in another order changes nothing.
+pub fn same(before: &Value, after: &Value) -> bool {
+    match (before.as_array(), after.as_array()) {
+        (Some(before), Some(after)) => {
+            let sorted = |items: &[Value]| {
+                let mut items: Vec<String> = items.iter().map(Value::to_string).collect();
+                items.sort();
+                items.dedup();
+                items
+            };
+            sorted(before) == sorted(after)
+        },
+        _ => before == after
+    }
+}
+
+/// The value a patch field has on the `current` resource.
+fn value_of(current: &Value, field: &str, fields: &[(&str, &str)]) -> Value {
+    let pointer = fields
+        .iter()
+        .find(|(f, _)| *f == field)
+        .map(|(_, p)| p.to_string())
+        .unwrap_or_else(|| format!("/{}", field));
+
+    normalize(current.pointer(&pointer).unwrap_or(&Value::Null))
+}
+
+/// The patch fields with their values on the `current` resource.
+pub fn current(current: &Value, fields: &[(&str, &str)]) -> Map<String, Value> {
+    fields
+        .iter()
+        .map(|(field, _)| (field.to_string(), value_of(current, field, fields)))
+        .collect()
+}
+
+/// The fields of `patch` that differ from the `current` resource. Fields the
+/// patch leaves out aren't changes.
+pub fn changes<T: Serialize>(current: &Value, patch: &T, fields: &[(&str, &str)]) -> Result<Vec<Change>> {
+    let patch = serde_json::to_value(patch)?;
+    let mut changes = Vec::new();
+
+    for (field, after) in patch.as_object().into_iter().flatten() {
+        let before = value_of(current, field, fields);
+
+        if !same(&before, after) {
+            changes.push(Change { field: field.to_string(), before, after: after.clone() });
+        }
+    }
+
+    Ok(changes)
+}
+
+/// A value as it reads in a diff.
+pub fn show(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.to_string(),
+        Value::Null => "(none)".to_string(),
+        other => other.to_string()
+    }
+}
+
+/// Print each change with the current value in red and the new one in green.
+pub fn print(changes: &[Change]) {
+    for change in changes {
+        eprintln!(
+            "  {}: {} → {}",
+            style(&change.field).bold(),
+            style(show(&change.before)).red(),
+            style(show(&change.after)).green()
+        );
+    }
+}
+
+/// Show an update's diff and decide whether to send it.
+/**
+  - Nothing is sent when nothing changes.
+  - Headless runs print the diff and go ahead, otherwise it has to be confirmed.
+  */
+pub fn review(what: &str, changes: &[Change], headless: bool) -> Result<bool> {
+    if changes.is_empty() {
+        eprintln!("No changes to {}, nothing was updated.", what);
+        return Ok(false);
+    }
+
+    eprintln!("Updating {}:", what);
+    print(changes);
+
+    if headless {
+        return Ok(true);
+    }
+
+    let apply = Confirm::new().with_prompt("Apply these changes?").default(true).interact()?;
+    if !apply {
+        eprintln!("Cancelled, nothing was updated.");
+    }
+
+    Ok(apply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wpe::{InstallPatch, UserPatch};
+
+    fn user() -> Value {
+        json!({
+            "roles": "partial",
+            "installs": [{ "id": "a", "name": "acmeprod" }, { "id": "b", "name": "acmestg" }]
+        })
+    }
+
+    #[test]
+    fn fields_left_out_of_the_patch_are_not_changes() {
+        let patch = UserPatch { roles: Some("full".to_string()), install_ids: None };
+        let changes = changes(&user(), &patch, USER_FIELDS).unwrap();
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].field, "roles");
+        assert_eq!((&changes[0].before, &changes[0].after), (&json!("partial"), &json!("full")));
+    }
+
+    #[test]
+    fn install_lists_are_compared_as_sets() {
+        let reordered = UserPatch { roles: None, install_ids: Some(vec!["b".to_string(), "a".to_string()]) };
+        assert!(changes(&user(), &reordered, USER_FIELDS).unwrap().is_empty());
+
+        let added = UserPatch { roles: None, install_ids: Some(vec!["a".to_string(), "c".to_string()]) };
+        let changes = changes(&user(), &added, USER_FIELDS).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].before, json!(["a", "b"]));
+    }
+
+    #[test]
+    fn fields_are_read_from_their_pointer() {
+        let install = json!({ "site": { "id": "s1" }, "environment": "staging" });
+        let patch = InstallPatch { site_id: Some("s1".to_string()), environment: Some("production".to_string()) };
+        let changes = changes(&install, &patch, INSTALL_FIELDS).unwrap();
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].field, "environment");
+    }
+
+    #[test]
+    fn missing_values_show_as_none() {
+        let domain = json!({ "primary": true, "redirects_to": [] });
+        assert_eq!(current(&domain, DOMAIN_FIELDS)["redirect_to"], Value::Null);
+        assert_eq!(show(&Value::Null), "(none)");
+        assert_eq!(show(&json!("acme")), "acme");
+    }
+}
@@ -0,0 +1,312 @@
+use std::fs;
+use std::path::Path;
+use clap::{Arg, Command, CommandFactory};
+use clap_mangen::Man;
+use anyhow::Result;
+use crate::output::write_stdout;
+use crate::cli::{Cli, DocsCommand};
+
+/// Headless examples for each command, keyed by the command's full name.
+const EXAMPLES: &[(&str, &[&str])] = &[
+    ("wpe", &[
+        "wpe sites list --output json",
+        "wpe installs list --filter environment=production --fields name,php_version",
+        "wpe --account acme installs list --sort php_version:desc"
+    ]),
+    ("wpe sites", &[
+        "wpe sites --account acme",
+        "wpe -H sites --filter name~acme --sort name"
+    ]),
+    ("wpe sites list", &[
+        "wpe sites list --page 2",
+        "wpe sites list --filter name~acme --output yaml"
+    ]),
+    ("wpe sites get", &["wpe sites get \"Acme Corp\" --output yaml"]),
+    ("wpe sites add", &["wpe sites add --account acme --name \"Acme Corp\""]),
+    ("wpe sites update", &["wpe sites update \"Acme Corp\" --name \"Acme Corporation\""]),
+    ("wpe sites delete", &["wpe sites delete \"Acme Corporation\" --yes"]),
+    ("wpe installs", &[
+        "wpe -H installs --filter php_version<8.1",
+        "wpe -H installs --output csv > installs.csv"
+    ]),
+    ("wpe installs list", &["wpe installs list --account acme --fields name,environment"]),
+    ("wpe installs get", &["wpe installs get acmeprod --query '.primary_domain'"]),
+    ("wpe installs add", &["wpe installs add --name acmestaging --site \"Acme Corp\" --environment staging"]),
+    ("wpe installs update", &[
+        "wpe installs update acmestaging --environment production",
+        "echo 'environment: staging' | wpe installs update acmeprod -f -"
+    ]),
+    ("wpe installs delete", &[
+        "wpe installs delete acmestaging --yes",
+        "wpe installs delete acmeprod --yes --force-production"
+    ]),
+    ("wpe accounts list", &["wpe accounts list --output json"]),
+    ("wpe accounts get", &["wpe accounts get acme"]),
+    ("wpe users list", &["wpe users list --account acme --fields email,roles"]),
+    ("wpe users add", &[
+        "wpe users add --account acme --email ann@example.com --first-name Ann --last-name Lee --roles partial --install acmeprod"
+    ]),
+    ("wpe users update", &["wpe users update ann@example.com --account acme --roles full"]),
+    ("wpe users delete", &["wpe users delete ann@example.com --account acme --yes"]),
+    ("wpe domains list", &["wpe domains list --install acmeprod --fields name,primary"]),
+    ("wpe domains add", &[
+        "wpe domains add --install acmeprod --name www.acme.com --primary",
+        "wpe domains add --install acmeprod -f domains.yaml"
+    ]),
+    ("wpe domains update", &["wpe domains update --install acmeprod acme.com --redirect-to <DOMAIN_ID>"]),
+    ("wpe import", &["wpe import clients.csv --concurrency 8 --yes"]),
+    ("wpe plan", &["wpe plan -f wpe.yaml --prune"]),
+    ("wpe apply", &["wpe apply -f wpe.yaml --yes"]),
+    ("wpe export", &["wpe export -o inventory.json", "wpe export --account acme -o acme.yaml"]),
+    ("wpe edit install", &["EDITOR=nano wpe edit install acmestaging"]),
+    ("wpe edit user", &["wpe edit user ann@example.com --account acme"]),
+    ("wpe ssh-keys add", &["wpe ssh-keys add --public-key \"$(cat ~/.ssh/id_ed25519.pub)\""]),
+    ("wpe backups create", &[
+        "wpe backups create --install acmeprod --description \"Before plugin update\" --email ops@example.com"
+    ]),
+    ("wpe backups list", &["wpe backups list --install acmeprod --output json"]),
+    ("wpe backups refresh", &["wpe backups refresh"]),
+    ("wpe cache purge", &["wpe cache purge --install acmeprod --type cdn"]),
+    ("wpe status", &["wpe status --output json"]),
+    ("wpe completions", &["source <(wpe completions bash)"]),
+    ("wpe docs man", &["wpe docs man --dir /usr/local/share/man/man1"]),
+    ("wpe docs markdown", &["wpe docs markdown > docs/reference.md"]),
+    ("wpe tree", &["wpe tree --account acme --depth 3", "wpe tree --output json"])
+];
+
+fn examples(name: &str) -> &'static [&'static str] {
+    EXAMPLES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, e)| *e)
+        .unwrap_or_default()
+}
+
+/// Full name of a built command, e.g. `wpe sites list`.
+fn full_name(cmd: &Command) -> String {
+    cmd.get_bin_name().unwrap_or_else(|| cmd.get_name()).to_string()
+}
+
+/// The documented subcommands, leaving out hidden ones and clap's own `help`.
+fn subcommands(cmd: &Command) -> impl Iterator<Item = &Command> {
+    cmd.get_subcommands().filter(|s| !s.is_hide_set() && s.get_name() != "help")
+}
+
+/// The root command with every subcommand's name and global flags filled in.
+fn built() -> Command {
+    let mut cmd = Cli::command().disable_help_subcommand(true);
+    cmd.build();
+    cmd
+}
+
+/// Escape text for roff, where backslashes and leading dots are special.
+fn roff_escape(line: &str) -> String {
+    let line = line.replace('\\', "\\e").replace('-', "\\-");
+    if line.starts_with('.') || line.starts_with('\'') {
+        format!("\\&{}", line)
+    } else {
+        line
+    }
+}
+
+/// Write a man page for `cmd` and every subcommand under it.
+fn write_man(cmd: &Command, dir: &Path, written: &mut Vec<String>) -> Result<()> {
+    for sub in subcommands(cmd) {
+        write_man(sub, dir, written)?;
+    }
+
+    let man = Man::new(cmd.clone());
+    let mut page = Vec::new();
+    man.render(&mut page)?;
+
+    let examples = examples(&full_name(cmd));
+    if !examples.is_empty() {
+        page.extend_from_slice(b".SH EXAMPLES\n.nf\n");
+        for example in examples {
+            page.extend_from_slice(format!("{}\n", roff_escape(example)).as_bytes());
+        }
+        page.extend_from_slice(b".fi\n");
+    }
+
+    let file = dir.join(man.get_filename());
+    fs::write(&file, page)?;
+    written.push(file.display().to_string());
+    Ok(())
+}
+
+/// How an argument is written in usage, e.g. `--output <FORMAT>` or `[PAGE]`.
+fn arg_usage(arg: &Arg) -> String {
+    let values = arg
+        .get_value_names()
+        .map(|names| names.iter().map(|n| format!("<{}>", n)).collect::<Vec<String>>().join(" "))
+        .unwrap_or_else(|| format!("<{}>", arg.get_id().as_str().to_uppercase()));
+
+    if arg.is_positional() {
+        let many = arg.get_num_args().is_some_and(|n| n.max_values() > 1);
+        let dots = if many { "..." } else { "" };
+        return if arg.is_required_set() {
+            format!("`{}{}`", values, dots)
+        } else {
+            format!("`[{}]{}`", values.trim_start_matches('<').trim_end_matches('>'), dots)
+        };
+    }
+
+    let mut names = Vec::new();
+    if let Some(short) = arg.get_short() {
+        names.push(format!("-{}", short));
+    }
+    if let Some(long) = arg.get_long() {
+        names.push(format!("--{}", long));
+    }
+    let names = names.join(", ");
+
+    if arg.get_action().takes_values() {
+        format!("`{} {}`", names, values)
+    } else {
+        format!("`{}`", names)
+    }
+}
+
+/// Help text for an argument with its allowed and default values, made safe for a table cell.
+fn arg_help(arg: &Arg) -> String {
+    let mut help = arg.get_help().map(|h| h.to_string()).unwrap_or_default();
+    if !help.is_empty() && !help.ends_with('.') {
+        help.push('.');
+    }
+
+    let possible: Vec<String> = arg
+        .get_possible_values()
+        .iter()
+        .filter(|v| !v.is_hide_set())
+        .map(|v| format!("`{}`", v.get_name()))
+        .collect();
+    if !possible.is_empty() {
+        help.push_str(&format!(" Possible values: {}.", possible.join(", ")));
+    }
+
+    // Flags default to off, which goes without saying.
+    let defaults: Vec<String> = arg
+        .get_default_values()
+        .iter()
+        .filter(|_| arg.get_action().takes_values())
+        .map(|v| format!("`{}`", v.to_string_lossy()))
+        .collect();
+    if !defaults.is_empty() {
+        help.push_str(&format!(" Default: {}.", defaults.join(", ")));
+    }
+
+    help.replace('|', "\\|").replace('\n', " ")
+}
+
+fn anchor(name: &str) -> String {
+    name.replace(' ', "-")
+}
+
+/// Append the markdown section for `cmd` and then each of its subcommands.
+/// Global flags are only listed on the command that defines them, `inherited`
+/// holds those of its parents.
+fn write_markdown(cmd: &Command, inherited: &[String], text: &mut String) {
+    let root = inherited.is_empty();
+    let name = full_name(cmd);
+    let level = if root { "#" } else { "##" };
+    text.push_str(&format!("{} {}\n\n", level, name));
+
+    if let Some(about) = cmd.get_about() {
+        text.push_str(&format!("{}\n\n", about));
+    }
+
+    let usage = cmd.clone().render_usage().to_string();
+    text.push_str(&format!("```\n{}\n```\n\n", usage.trim_start_matches("Usage: ")));
+
+    if let Some(after) = cmd.get_after_help() {
+        text.push_str(&format!("{}\n\n", after));
+    }
+
+    let args: Vec<&Arg> = cmd
+        .get_arguments()
+        .filter(|a| !a.is_hide_set() && !inherited.contains(&a.get_id().to_string()))
+        .collect();
+
+    let positionals: Vec<&&Arg> = args.iter().filter(|a| a.is_positional()).collect();
+    if !positionals.is_empty() {
+        text.push_str("| Argument | Description |\n| --- | --- |\n");
+        for arg in positionals {
+            text.push_str(&format!("| {} | {} |\n", arg_usage(arg), arg_help(arg)));
+        }
+        text.push('\n');
+    }
+
+    let options: Vec<&&Arg> = args.iter().filter(|a| !a.is_positional()).collect();
+    if !options.is_empty() {
+        text.push_str("| Option | Description |\n| --- | --- |\n");
+        for arg in options {
+            text.push_str(&format!("| {} | {} |\n", arg_usage(arg), arg_help(arg)));
+        }
+        text.push('\n');
+    }
+
+    let subs: Vec<&Command> = subcommands(cmd).collect();
+    if !subs.is_empty() {
+        text.push_str("| Command | Description |\n| --- | --- |\n");
+        for sub in &subs {
+            let sub_name = full_name(sub);
+            text.push_str(&format!(
+                "| [`{}`](#{}) | {} |\n",
+                sub_name,
+                anchor(&sub_name),
+                sub.get_about().map(|a| a.to_string()).unwrap_or_default()
+            ));
+        }
+        text.push('\n');
+    }
+
+    let examples = examples(&name);
+    if !examples.is_empty() {
+        text.push_str("**Examples**\n\n```bash\n");
+        for example in examples {
+            text.push_str(&format!("{}\n", example));
+        }
+        text.push_str("```\n\n");
+    }
+
+    let mut globals = inherited.to_vec();
+    globals.extend(cmd.get_arguments().filter(|a| a.is_global_set()).map(|a| a.get_id().to_string()));
+    // `help` is added to every command, keep it out of the subcommand tables too.
+    globals.push("help".to_string());
+
+    for sub in subs {
+        write_markdown(sub, &globals, text);
+    }
+}
+
+/// Provides logic for the docs command.
+///
+/// # Arguments
+///
+/// * `command` - &DocsCommand
+pub fn init(command: &DocsCommand) -> Result<()> {
+    let cmd = built();
+
+    match command {
+        DocsCommand::Man { dir } => {
+            let dir = Path::new(dir);
+            fs::create_dir_all(dir)?;
+
+            let mut written = Vec::new();
+            write_man(&cmd, dir, &mut written)?;
+            written.sort();
+
+            write_stdout(&format!("{}\n", written.join("\n")))
+        },
+        DocsCommand::Markdown { out } => {
+            let mut text = String::new();
+            write_markdown(&cmd, &[], &mut text);
+            let text = format!("{}\n", text.trim_end());
+
+            match out {
+                Some(file) => Ok(fs::write(file, text)?),
+                None => write_stdout(&text)
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use anyhow::Result;
+use wpe::*;
+use serde_json::Value;
+use crate::output::{Output, Kind};
+use crate::cli::{DomainsArgs, DomainCommand};
+use crate::guard::{self, Guard, Impact};
+use crate::diff;
+use crate::input::{Bodies, Update};
+
+/// Find a domain of an install by ID or name.
+pub fn resolve_domain(api: &API, install_id: &str, input: &str) -> Result<String> {
+    if is_uuid(input) {
+        return Ok(input.to_string());
+    }
+
+    api.get_all_domains(install_id)?
+        .iter()
+        .find(|d| d["name"].as_str().is_some_and(|n| n.eq_ignore_ascii_case(input)))
+        .and_then(|d| d["id"].as_str().map(String::from))
+        .ok_or_else(|| CliError::new(ErrorKind::NotFound, format!("No domain named '{}' was found.", input)).into())
+}
+
+fn install_of(command: &DomainCommand) -> &str {
+    match command {
+        DomainCommand::List { install, .. }
+        | DomainCommand::Get { install, .. }
+        | DomainCommand::Add { install, .. }
+        | DomainCommand::Update { install, .. }
+        | DomainCommand::Delete { install, .. } => install
+    }
+}
+
+/// Update a domain once its diff is reviewed. Returns null when nothing was sent.
+fn update(api: &API, install_id: &str, id: &str, data: &DomainPatch, headless: bool) -> Result<Value> {
+    let current = api.get_domain_by_id(install_id, id)?;
+    let what = format!("domain '{}'", current["name"].as_str().unwrap_or(id));
+    if !diff::review(&what, &diff::changes(&current, data, diff::DOMAIN_FIELDS)?, headless)? {
+        return Ok(Value::Null);
+    }
+
+    api.update_domain(install_id, id, data)
+}
+
+/// Provides logic for the domains command.
+///
+/// # Arguments
+///
+/// * `args` - &DomainsArgs
+/// * `api` - &API
+/// * `headless` - bool
+/// * `out` - &Output
+pub fn init(args: &DomainsArgs, api: &API, headless: bool, out: &Output) -> Result<()> {
+    let install_id = api.resolve(Resource::Install, install_of(&args.command))?;
+
+    match &args.command {
+        DomainCommand::List { page, .. } => {
+            let domains = api.get_domains(&install_id, Some(*page))?;
+            out.print(&domains["results"], Kind::Domains)?;
+        },
+        DomainCommand::Get { domain, .. } => {
+            let domain_id = resolve_domain(api, &install_id, domain)?;
+            let domain = api.get_domain_by_id(&install_id, &domain_id)?;
+            out.print(&domain, Kind::Domains)?;
+        },
+        DomainCommand::Add { name, primary, body, .. } => {
+            let bodies = Bodies::from(&body.file, || Ok(Domain {
+                name: name.clone().unwrap_or_default(),
+                primary: *primary
+            }))?;
+
+            let add_domain = bodies.items
+                .iter()
+                .map(|data| api.add_domain(&install_id, data))
+                .collect::<Result<Vec<_>>>()?;
+
+            out.print(&bodies.output(add_domain), Kind::Domains)?;
+        },
+        DomainCommand::Update { domain, primary, redirect_to, body, .. } => {
+            let bodies = Bodies::from(&body.file, || {
+                if primary.is_none() && redirect_to.is_none() {
+                    return Err(CliError::new(ErrorKind::Usage, "Nothing to update, pass --primary or --redirect-to.").into());
+                }
+                Ok(Update::new(DomainPatch { primary: *primary, redirect_to: redirect_to.clone() }))
+            })?;
+
+            let mut update_domain = Vec::new();
+            for body in &bodies.items {
+                let domain_id = resolve_domain(api, &install_id, body.target(domain.as_deref())?)?;
+                update_domain.push(update(api, &install_id, &domain_id, &body.patch, headless)?);
+            }
+
+            out.print(&bodies.output(update_domain), Kind::Domains)?;
+        },
+        DomainCommand::Delete { domain, yes, .. } => {
+            let domain_id = resolve_domain(api, &install_id, domain)?;
+            let name = api.get_domain_by_id(&install_id, &domain_id)?["name"].as_str().unwrap_or(&domain_id).to_string();
+            let guard = Guard { yes: *yes, force_production: false, headless };
+            guard::confirm(&Impact::new("domain", &name), &guard)?;
+
+            api.delete_domain(&install_id, &domain_id)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,298 @@
+use std::{env, fs, io::Write, process::Command};
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+use wpe::*;
+use crate::output::{Output, Kind};
+use crate::cli::{EditCommand, Environment};
+use crate::{accounts, diff, domains, users};
+
+/// The command the edited file is opened with.
+fn editor() -> String {
+    ["WPE_EDITOR", "VISUAL", "EDITOR"]
+        .iter()
+        .find_map(|var| env::var(var).ok().filter(|e| !e.trim().is_empty()))
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+/// Write `text` under `header` to a temporary file, open it in the editor and read it back.
+/**
+  - The file is created with a random name that can't already exist, readable only by the user.
+  - It is removed once read back, or on any error before that.
+  */
+fn open(header: &str, text: &str) -> Result<String> {
+    let mut file = tempfile::Builder::new().prefix("wpe-edit-").suffix(".yaml").tempfile()?;
+    file.write_all(format!("{}{}", header, text).as_bytes())?;
+    file.flush()?;
+
+    let editor = editor();
+    let mut words = shlex::split(&editor).unwrap_or_default().into_iter();
+    let program = words
+        .next()
+        .ok_or_else(|| CliError::new(ErrorKind::Usage, format!("Can't run the editor '{}'.", editor)))?;
+    let status = Command::new(&program).args(words).arg(file.path()).status();
+
+    // Editors that save by replacing the file leave a new one at the same path.
+    let edited = fs::read_to_string(file.path());
+    drop(file);
+
+    let status = status.map_err(|e| CliError::new(ErrorKind::Usage, format!("Can't run the editor '{}': {}.", program, e)))?;
+    if !status.success() {
+        return Err(CliError::new(ErrorKind::Other, format!("The editor exited with {}, nothing was updated.", status)).into());
+    }
+
+    Ok(edited?)
+}
+
+/// The comments above the fields, with the error of the last attempt if there was one.
+fn header(what: &str, error: Option<&str>) -> String {
+    let mut header = format!(
+        "# Editing {}. Only the fields below can be changed, lines starting with '#' are ignored.\n\
+         # Save and close the editor to send the changes, or empty the file to cancel.\n",
+        what
+    );
+    if let Some(error) = error {
+        header.push_str("#\n");
+        for line in error.lines() {
+            header.push_str(&format!("# error: {}\n", line));
+        }
+    }
+
+    header + "#\n"
+}
+
+/// The edited text without the header, so a banner isn't repeated when it is opened again.
+fn body(text: &str) -> String {
+    text.lines()
+        .skip_while(|line| line.starts_with('#'))
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+/// The edited fields that differ from the `current` resource.
+fn parse(text: &str, current: &Value, fields: &[(&str, &str)]) -> Result<Map<String, Value>> {
+    let edited: Value = serde_yaml::from_str(text)?;
+    let Value::Object(edited) = edited else {
+        return Err(CliError::new(ErrorKind::Validation, "Expected the fields as `name: value` lines.").into());
+    };
+    let names: Vec<&str> = fields.iter().map(|(f, _)| *f).collect();
+    if let Some(unknown) = edited.keys().find(|k| !names.contains(&k.as_str())) {
+        return Err(CliError::new(
+            ErrorKind::Validation,
+            format!("'{}' can't be edited, the fields are: {}.", unknown, names.join(", "))
+        ).into());
+    }
+
+    let current = diff::current(current, fields);
+    Ok(edited.into_iter().filter(|(k, v)| !current.get(k).is_some_and(|c| diff::same(c, v))).collect())
+}
+
+/// Deserialize the changed fields into a patch.
+fn patch<P: DeserializeOwned>(fields: Map<String, Value>) -> Result<P> {
+    serde_json::from_value(Value::Object(fields))
+        .map_err(|e| CliError::new(ErrorKind::Validation, e.to_string()).into())
+}
+
+/// A string field that was edited to be empty.
+fn required(field: &str, value: &Option<String>) -> Result<()> {
+    if value.as_deref().is_some_and(|v| v.trim().is_empty()) {
+        return Err(CliError::new(ErrorKind::Validation, format!("'{}' can't be empty.", field)).into());
+    }
+
+    Ok(())
+}
+
+/// Edit the `fields` of a resource until they are valid, and send them.
+/**
+  - `build` turns the changed fields into a patch, its errors are shown in the file.
+  - The API rejecting the patch opens the file again too, any other error is returned.
+  - Saving it unchanged after an error returns that error.
+  - Returns the updated resource, or `None` when nothing was sent.
+  */
+fn edit<P, B, S>(what: &str, current: &Value, fields: &[(&str, &str)], build: B, send: S) -> Result<Option<Value>>
+where
+    P: Serialize,
+    B: Fn(Map<String, Value>) -> Result<P>,
+    S: Fn(&P) -> Result<Value>
+{
+    let mut text = serde_yaml::to_string(&diff::current(current, fields))?;
+    let mut error: Option<String> = None;
+
+    loop {
+        let edited = body(&open(&header(what, error.as_deref()), &text)?);
+        if edited.trim().is_empty() {
+            eprintln!("Edit cancelled, nothing was updated.");
+            return Ok(None);
+        }
+        // Closing the editor without fixing the error gives up on it.
+        if let Some(error) = error.filter(|_| edited == text) {
+            return Err(CliError::new(ErrorKind::Validation, format!("{}, nothing was updated.", error.trim_end_matches('.'))).into());
+        }
+        text = edited;
+
+        let patch = match parse(&text, current, fields).and_then(&build) {
+            Ok(patch) => patch,
+            Err(err) => {
+                error = Some(format!("{:#}", err));
+                continue;
+            }
+        };
+
+        // Saving the file is the confirmation, so the diff is only shown.
+        if !diff::review(what, &diff::changes(current, &patch, fields)?, true)? {
+            return Ok(None);
+        }
+
+        match send(&patch) {
+            Err(err) if CliError::kind_of(&err) == ErrorKind::Validation => error = Some(format!("{:#}", err)),
+            result => return result.map(Some)
+        }
+    }
+}
+
+/// Provides logic for the edit command.
+///
+/// # Arguments
+///
+/// * `command` - &EditCommand
+/// * `api` - &API
+/// * `headless` - bool
+/// * `out` - &Output
+/// * `account` - Option<&str>
+pub fn init(command: &EditCommand, api: &API, headless: bool, out: &Output, account: Option<&str>) -> Result<()> {
+    if headless {
+        return Err(CliError::new(
+            ErrorKind::Usage,
+            "wpe edit opens an editor, use the update verbs in headless mode."
+        ).into());
+    }
+
+    match command {
+        EditCommand::Site { site } => {
+            let id = api.resolve(Resource::Site, site)?;
+            let current = api.get_site_by_id(&id)?;
+            let what = format!("site '{}'", current["name"].as_str().unwrap_or(&id));
+
+            let updated = edit(&what, &current, diff::SITE_FIELDS,
+                |fields| {
+                    let data: SitePatch = patch(fields)?;
+                    required("name", &data.name)?;
+                    Ok(data)
+                },
+                |data| api.update_site(&id, data)
+            )?;
+            if let Some(site) = updated {
+                out.print(&site, Kind::Sites)?;
+            }
+        },
+        EditCommand::Install { install } => {
+            let id = api.resolve(Resource::Install, install)?;
+            let current = api.get_install_by_id(&id)?;
+            let what = format!("install '{}'", current["name"].as_str().unwrap_or(&id));
+
+            let updated = edit(&what, &current, diff::INSTALL_FIELDS,
+                |fields| {
+                    let mut data: InstallPatch = patch(fields)?;
+                    data.environment = data.environment
+                        .map(|e| Environment::from_str(&e, true)
+                            .map(|e| e.as_str().to_string())
+                            .map_err(|_| CliError::new(
+                                ErrorKind::Validation,
+                                format!("'{}' isn't an environment, use production, staging or development.", e)
+                            )))
+                        .transpose()?;
+                    // A site can be given by name, it is sent as its ID.
+                    data.site_id = data.site_id.map(|s| api.resolve(Resource::Site, &s)).transpose()?;
+                    Ok(data)
+                },
+                |data| api.update_install(&id, data)
+            )?;
+            if let Some(install) = updated {
+                out.print(&install, Kind::Installs)?;
+            }
+        },
+        EditCommand::Domain { install, domain } => {
+            let install_id = api.resolve(Resource::Install, install)?;
+            let id = domains::resolve_domain(api, &install_id, domain)?;
+            let current = api.get_domain_by_id(&install_id, &id)?;
+            let what = format!("domain '{}'", current["name"].as_str().unwrap_or(&id));
+
+            let updated = edit(&what, &current, diff::DOMAIN_FIELDS,
+                |fields| {
+                    let mut data: DomainPatch = patch(fields)?;
+                    data.redirect_to = data.redirect_to.map(|d| domains::resolve_domain(api, &install_id, &d)).transpose()?;
+                    Ok(data)
+                },
+                |data| api.update_domain(&install_id, &id, data)
+            )?;
+            if let Some(domain) = updated {
+                out.print(&domain, Kind::Domains)?;
+            }
+        },
+        EditCommand::User { user } => {
+            let account_id = &accounts::require(api, account, headless, "edit a user")?;
+            let id = users::resolve_user(api, account_id, user)?;
+            let current = api.get_user_by_id(account_id, &id)?;
+            let what = format!("user '{}'", current["email"].as_str().unwrap_or(&id));
+
+            let updated = edit(&what, &current, diff::USER_FIELDS,
+                |fields| {
+                    let mut data: UserPatch = patch(fields)?;
+                    required("roles", &data.roles)?;
+                    data.install_ids = data.install_ids.map(|i| users::resolve_installs(api, &i)).transpose()?;
+                    Ok(data)
+                },
+                |data| api.update_user(account_id, &id, &AccountUserPatch { user: data.clone() })
+            )?;
+            if let Some(user) = updated {
+                out.print(&user, Kind::Users)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn install() -> Value {
+        json!({ "id": "i1", "name": "acmeprod", "site": { "id": "s1" }, "environment": "production" })
+    }
+
+    #[test]
+    fn the_header_and_its_error_are_stripped() {
+        let text = format!("{}site_id: s1\n# kept, yaml ignores it\nenvironment: staging\n", header("install 'acmeprod'", Some("bad\nworse")));
+        assert_eq!(body(&text), "site_id: s1\n# kept, yaml ignores it\nenvironment: staging\n");
+        assert_eq!(body("# only comments\n#\n"), "");
+    }
+
+    #[test]
+    fn unchanged_fields_are_left_out_of_the_patch() {
+        let changed = parse("site_id: s1\nenvironment: staging\n", &install(), diff::INSTALL_FIELDS).unwrap();
+        assert_eq!(Value::Object(changed), json!({ "environment": "staging" }));
+
+        let unchanged = parse("site_id: s1\nenvironment: production\n", &install(), diff::INSTALL_FIELDS).unwrap();
+        assert!(unchanged.is_empty());
+    }
+
+    #[test]
+    fn bad_values_are_shown_in_the_banner() {
+        let err = parse("name: acme\n", &install(), diff::INSTALL_FIELDS).err().unwrap();
+        assert_eq!(CliError::kind_of(&err), ErrorKind::Validation);
+        let banner = header("install 'acmeprod'", Some(&format!("{:#}", err)));
+        assert!(banner.ends_with("# error: 'name' can't be edited, the fields are: site_id, environment.\n#\n"), "{}", banner);
+
+        let err = parse("- site_id\n", &install(), diff::INSTALL_FIELDS).err().unwrap();
+        assert_eq!(err.to_string(), "Expected the fields as `name: value` lines.");
+
+        let err = patch::<InstallPatch>(parse("environment: [staging]\n", &install(), diff::INSTALL_FIELDS).unwrap())
+            .err()
+            .unwrap();
+        let banner = header("install 'acmeprod'", Some(&format!("{:#}", err)));
+        assert!(banner.contains("#\n# error: invalid type: sequence, expected a string\n#\n"), "{}", banner);
+    }
+}
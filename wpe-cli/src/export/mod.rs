@@ -0,0 +1,124 @@
+use std::{fs, path::Path};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use wpe::*;
+use crate::output::{Output, Kind};
+
+/// Bumped when the layout of the snapshot changes.
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// How many of each resource a snapshot holds.
+#[derive(Serialize, Default)]
+struct Counts {
+    accounts: usize,
+    sites: usize,
+    installs: usize,
+    domains: usize,
+    users: usize,
+    ssh_keys: usize
+}
+
+/// Every resource as the API returns it. Accounts hold their users and installs hold their domains.
+#[derive(Serialize)]
+struct Snapshot {
+    version: u32,
+    exported_at: DateTime<Utc>,
+    /// The account the export was narrowed to with `--account`.
+    account: Option<String>,
+    counts: Counts,
+    accounts: Vec<Value>,
+    sites: Vec<Value>,
+    installs: Vec<Value>,
+    ssh_keys: Vec<Value>
+}
+
+/// Walk every listing, a page at a time.
+fn build(api: &API, account: Option<&str>) -> Result<Snapshot> {
+    let mut counts = Counts::default();
+
+    eprintln!("Exporting accounts...");
+    let mut accounts: Vec<Value> = api
+        .get_all_accounts()?
+        .into_iter()
+        .filter(|a| account.is_none_or(|id| a["id"] == id))
+        .collect();
+    for account in &mut accounts {
+        let id = account["id"].as_str().unwrap_or_default().to_string();
+        let users = api
+            .get_all_account_users(&id)
+            .with_context(|| format!("Unable to export the users of account {}", id))?;
+        counts.users += users.len();
+        account["users"] = Value::Array(users);
+    }
+
+    eprintln!("Exporting sites...");
+    let sites = api.get_all_sites(account)?;
+
+    eprintln!("Exporting installs...");
+    let mut installs = api.get_all_installs(account)?;
+    eprintln!("Exporting domains of {} installs...", installs.len());
+    for install in &mut installs {
+        let id = install["id"].as_str().unwrap_or_default().to_string();
+        let domains = api
+            .get_all_domains(&id)
+            .with_context(|| format!("Unable to export the domains of install {}", id))?;
+        counts.domains += domains.len();
+        install["domains"] = Value::Array(domains);
+    }
+
+    eprintln!("Exporting ssh keys...");
+    let ssh_keys = api.get_all_ssh_keys()?;
+
+    counts.accounts = accounts.len();
+    counts.sites = sites.len();
+    counts.installs = installs.len();
+    counts.ssh_keys = ssh_keys.len();
+
+    Ok(Snapshot {
+        version: SNAPSHOT_VERSION,
+        exported_at: Utc::now(),
+        account: account.map(str::to_string),
+        counts,
+        accounts,
+        sites,
+        installs,
+        ssh_keys
+    })
+}
+
+/// Write the snapshot as YAML for a `.yaml` or `.yml` path, otherwise as JSON.
+fn save(path: &Path, snapshot: &Snapshot) -> Result<()> {
+    let text = match path.extension().and_then(|e| e.to_str()) {
+        Some("yaml" | "yml") => serde_yaml::to_string(snapshot)?,
+        _ => serde_json::to_string_pretty(snapshot)? + "\n"
+    };
+
+    fs::write(path, text).with_context(|| format!("Unable to write {}", path.display()))
+}
+
+/// Provides logic for the export command.
+///
+/// # Arguments
+///
+/// * `file` - Option<&str>
+/// * `api` - &API
+/// * `out` - &Output
+/// * `account` - Option<&str>
+pub fn init(file: Option<&str>, api: &API, out: &Output, account: Option<&str>) -> Result<()> {
+    let snapshot = build(api, account)?;
+
+    let Some(file) = file else {
+        return out.print(&serde_json::to_value(&snapshot)?, Kind::Other);
+    };
+
+    save(Path::new(file), &snapshot)?;
+    let counts = &snapshot.counts;
+    eprintln!(
+        "Exported {} accounts, {} sites, {} installs, {} domains, {} users and {} ssh keys to {}.",
+        counts.accounts, counts.sites, counts.installs, counts.domains, counts.users, counts.ssh_keys, file
+    );
+
+    Ok(())
+}
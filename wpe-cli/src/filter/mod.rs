@@ -0,0 +1,268 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+use clap::Args;
+use serde_json::Value;
+use anyhow::{anyhow, Error, Result};
+
+/// Comparison operators understood by `--filter`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Contains,
+    NotContains,
+    Lt,
+    Le,
+    Gt,
+    Ge
+}
+
+// Two character operators must be tried before their one character prefixes.
+const OPS: [(&str, Op); 8] = [
+    ("!=", Op::Ne),
+    ("!~", Op::NotContains),
+    ("<=", Op::Le),
+    (">=", Op::Ge),
+    ("=", Op::Eq),
+    ("~", Op::Contains),
+    ("<", Op::Lt),
+    (">", Op::Gt)
+];
+
+/// A single `field<op>value` expression, e.g. `php_version<8.1`.
+#[derive(Clone, Debug)]
+pub struct Filter {
+    field: String,
+    op: Op,
+    value: String
+}
+
+impl FromStr for Filter {
+    type Err = Error;
+
+    fn from_str(expression: &str) -> Result<Self> {
+        // Split on whichever operator appears first in the expression.
+        let (index, token, op) = OPS
+            .iter()
+            .filter_map(|(token, op)| expression.find(token).map(|i| (i, *token, *op)))
+            .min_by_key(|(i, token, _)| (*i, std::cmp::Reverse(token.len())))
+            .ok_or_else(|| anyhow!(
+                "Invalid filter '{}', expected <field><op><value> with one of = != ~ !~ < <= > >=",
+                expression
+            ))?;
+
+        let field = expression[..index].trim();
+        if field.is_empty() {
+            return Err(anyhow!("Invalid filter '{}', missing a field name", expression));
+        }
+
+        Ok(Self {
+            field: field.to_string(),
+            op,
+            value: expression[index + token.len()..].trim().to_string()
+        })
+    }
+}
+
+impl Filter {
+    /// A missing or null field only matches `!=`, so e.g. `php_version<8.1`
+    /// doesn't pick up records that have no PHP version at all.
+    fn matches(&self, item: &Value) -> bool {
+        let Some(actual) = field_text(item, &self.field) else {
+            return self.op == Op::Ne;
+        };
+        match self.op {
+            Op::Eq => actual.eq_ignore_ascii_case(&self.value),
+            Op::Ne => !actual.eq_ignore_ascii_case(&self.value),
+            Op::Contains => actual.to_lowercase().contains(&self.value.to_lowercase()),
+            Op::NotContains => !actual.to_lowercase().contains(&self.value.to_lowercase()),
+            Op::Lt => compare(&actual, &self.value) == Ordering::Less,
+            Op::Le => compare(&actual, &self.value) != Ordering::Greater,
+            Op::Gt => compare(&actual, &self.value) == Ordering::Greater,
+            Op::Ge => compare(&actual, &self.value) != Ordering::Less
+        }
+    }
+}
+
+/// A `--sort field[:desc]` option.
+#[derive(Clone, Debug)]
+pub struct Sort {
+    field: String,
+    descending: bool
+}
+
+impl FromStr for Sort {
+    type Err = Error;
+
+    fn from_str(expression: &str) -> Result<Self> {
+        let (field, direction) = match expression.rsplit_once(':') {
+            Some((field, direction)) => (field, direction),
+            None => (expression, "asc")
+        };
+
+        let descending = match direction.to_lowercase().as_str() {
+            "asc" => false,
+            "desc" => true,
+            _ => return Err(anyhow!("Invalid sort direction '{}', expected asc or desc", direction))
+        };
+
+        Ok(Self { field: field.trim().to_string(), descending })
+    }
+}
+
+/// The filters and sort order requested for a listing, read from `--filter` and `--sort`.
+#[derive(Args, Clone, Debug, Default)]
+pub struct Criteria {
+    /// Filter results, e.g. name~acme, environment=production, php_version<8.1
+    #[arg(long = "filter", value_name = "EXPR", global = true)]
+    filters: Vec<Filter>,
+    /// Sort results by a field, append :desc to reverse
+    #[arg(long, value_name = "FIELD", global = true)]
+    sort: Option<Sort>
+}
+
+impl Criteria {
+    /// When nothing was asked for, a single page can be shown as is.
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty() && self.sort.is_none()
+    }
+
+    /// Sorting can only be done once every page has been fetched.
+    pub fn is_sorted(&self) -> bool {
+        self.sort.is_some()
+    }
+
+    pub fn matches(&self, item: &Value) -> bool {
+        self.filters.iter().all(|f| f.matches(item))
+    }
+
+    /// Filter and sort a full result set.
+    pub fn apply(&self, items: Vec<Value>) -> Vec<Value> {
+        let mut items: Vec<Value> = items.into_iter().filter(|i| self.matches(i)).collect();
+
+        if let Some(sort) = &self.sort {
+            items.sort_by(|a, b| {
+                let text = |item: &Value| field_text(item, &sort.field).unwrap_or_default();
+                let order = compare(&text(a), &text(b));
+                if sort.descending { order.reverse() } else { order }
+            });
+        }
+
+        items
+    }
+}
+
+/// Read a dotted field as text, `None` when it is missing or null. References to
+/// other resources such as `account` or `site` are objects, so those compare by their ID.
+fn field_text(item: &Value, field: &str) -> Option<String> {
+    let pointer = format!("/{}", field.replace('.', "/"));
+    match item.pointer(&pointer)? {
+        Value::Null => None,
+        Value::String(s) => Some(s.to_string()),
+        Value::Object(map) => match map.get("id") {
+            Some(Value::String(id)) => Some(id.to_string()),
+            _ => Some(Value::Object(map.clone()).to_string())
+        },
+        v => Some(v.to_string())
+    }
+}
+
+/// Compare dotted numbers like PHP versions numerically, anything else as text.
+fn compare(a: &str, b: &str) -> Ordering {
+    let numeric = |s: &str| -> Option<Vec<u64>> {
+        s.split('.').map(|part| part.parse::<u64>().ok()).collect()
+    };
+
+    match (numeric(a), numeric(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        _ => a.to_lowercase().cmp(&b.to_lowercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn filter(expression: &str) -> Filter {
+        expression.parse().unwrap()
+    }
+
+    #[test]
+    fn two_character_operators_win_over_their_prefixes() {
+        assert_eq!(filter("php_version>=8.1").op, Op::Ge);
+        assert_eq!(filter("php_version<=8.1").op, Op::Le);
+        assert_eq!(filter("name!=acme").op, Op::Ne);
+        assert_eq!(filter("name!~acme").op, Op::NotContains);
+        assert_eq!(filter("php_version>8.1").op, Op::Gt);
+    }
+
+    #[test]
+    fn splits_on_the_first_operator() {
+        let f = filter("name=a>b");
+        assert_eq!((f.field.as_str(), f.op, f.value.as_str()), ("name", Op::Eq, "a>b"));
+
+        let f = filter(" environment = production ");
+        assert_eq!((f.field.as_str(), f.value.as_str()), ("environment", "production"));
+    }
+
+    #[test]
+    fn rejects_filters_without_an_operator_or_field() {
+        assert!("name".parse::<Filter>().is_err());
+        assert!("=acme".parse::<Filter>().is_err());
+    }
+
+    #[test]
+    fn parses_sort_directions() {
+        let sort: Sort = "name".parse().unwrap();
+        assert_eq!((sort.field.as_str(), sort.descending), ("name", false));
+
+        let sort: Sort = "account.id:DESC".parse().unwrap();
+        assert_eq!((sort.field.as_str(), sort.descending), ("account.id", true));
+
+        assert!("name:up".parse::<Sort>().is_err());
+    }
+
+    #[test]
+    fn compares_versions_numerically_and_text_case_insensitively() {
+        assert_eq!(compare("8.10", "8.9"), Ordering::Greater);
+        assert_eq!(compare("7.4", "8.0"), Ordering::Less);
+        assert_eq!(compare("8.1", "8.1"), Ordering::Equal);
+        assert_eq!(compare("Acme", "acme"), Ordering::Equal);
+        assert_eq!(compare("b", "A"), Ordering::Greater);
+        // Only one side is a number, so both compare as text.
+        assert_eq!(compare("8.1", "latest"), Ordering::Less);
+    }
+
+    #[test]
+    fn missing_fields_only_match_not_equal() {
+        let item = json!({ "name": "acmeprod", "php_version": null });
+
+        assert!(!filter("php_version<8.1").matches(&item));
+        assert!(!filter("php_version=").matches(&item));
+        assert!(!filter("missing~acme").matches(&item));
+        assert!(filter("php_version!=8.1").matches(&item));
+        assert!(filter("missing!=8.1").matches(&item));
+    }
+
+    #[test]
+    fn matches_nested_references_by_id() {
+        let item = json!({ "site": { "id": "abc", "name": "Acme" }, "php_version": "7.4" });
+
+        assert!(filter("site=abc").matches(&item));
+        assert!(filter("site.name~acm").matches(&item));
+        assert!(filter("php_version<8.1").matches(&item));
+    }
+
+    #[test]
+    fn sorts_in_either_direction() {
+        let items = vec![json!({ "v": "8.0" }), json!({ "v": "7.4" }), json!({ "v": "8.10" })];
+        let sorted = |sort: &str| -> Vec<Value> {
+            let criteria = Criteria { filters: Vec::new(), sort: Some(sort.parse().unwrap()) };
+            criteria.apply(items.clone()).iter().map(|i| i["v"].clone()).collect()
+        };
+
+        assert_eq!(sorted("v"), vec![json!("7.4"), json!("8.0"), json!("8.10")]);
+        assert_eq!(sorted("v:desc"), vec![json!("8.10"), json!("8.0"), json!("7.4")]);
+    }
+}
@@ -0,0 +1,168 @@
+use console::style;
+use dialoguer::Input;
+use anyhow::Result;
+use serde_json::Value;
+use wpe::*;
+
+/// What a delete removes, shown before it is confirmed.
+pub struct Impact {
+    /// What is being deleted, e.g. "site".
+    pub kind: &'static str,
+    /// The name that has to be typed back to confirm.
+    pub name: String,
+    /// One line per resource that goes with it.
+    pub lines: Vec<String>,
+    /// Whether a production install is among them.
+    pub production: bool
+}
+
+impl Impact {
+    /// A delete that only removes the resource itself.
+    pub fn new(kind: &'static str, name: &str) -> Self {
+        Self { kind, name: name.to_string(), lines: Vec::new(), production: false }
+    }
+
+    /// A site along with every install under it and their domains.
+    pub fn site(api: &API, id: &str) -> Result<Self> {
+        let site = api.get_site_by_id(id)?;
+        let mut impact = Self::new("site", site["name"].as_str().unwrap_or(id));
+
+        for install in site["installs"].as_array().into_iter().flatten() {
+            impact.add_install(api, install, true)?;
+        }
+
+        Ok(impact)
+    }
+
+    /// An install and its domains.
+    pub fn install(api: &API, id: &str) -> Result<Self> {
+        let install = api.get_install_by_id(id)?;
+        let mut impact = Self::new("install", install["name"].as_str().unwrap_or(id));
+        impact.add_install(api, &install, false)?;
+
+        Ok(impact)
+    }
+
+    /// Add an install's environment and domains, under a line of its own when `nested` in a site.
+    fn add_install(&mut self, api: &API, install: &Value, nested: bool) -> Result<()> {
+        let environment = install["environment"].as_str().unwrap_or_default();
+        let environment = if environment == "production" {
+            self.production = true;
+            style(environment).red().bold().to_string()
+        } else {
+            environment.to_string()
+        };
+        let indent = if nested {
+            self.lines.push(format!("install {} ({})", install["name"].as_str().unwrap_or_default(), environment));
+            "  "
+        } else {
+            self.lines.push(format!("environment {}", environment));
+            ""
+        };
+
+        for domain in api.get_all_domains(install["id"].as_str().unwrap_or_default())? {
+            self.lines.push(format!("{}domain {}", indent, domain["name"].as_str().unwrap_or_default()));
+        }
+
+        Ok(())
+    }
+
+    /// Print the summary to stderr, keeping stdout for command output.
+    pub fn print(&self) {
+        eprintln!("{} {} {}", style("This will delete").bold(), self.kind, style(&self.name).bold());
+        for line in &self.lines {
+            eprintln!("  {}", line);
+        }
+    }
+}
+
+/// The confirmation flags of a delete command.
+pub struct Guard {
+    pub yes: bool,
+    pub force_production: bool,
+    pub headless: bool
+}
+
+/// Show what will be deleted and confirm it.
+/**
+  - Production installs always need `--force-production`.
+  - `--yes` skips the prompt, and headless runs can't go without it.
+  - Otherwise the name has to be typed back.
+  */
+pub fn confirm(impact: &Impact, guard: &Guard) -> Result<()> {
+    impact.print();
+
+    if impact.production && !guard.force_production {
+        return Err(CliError::new(
+            ErrorKind::Usage,
+            format!("The {} '{}' includes a production install, pass --force-production to delete it.", impact.kind, impact.name)
+        ).into());
+    }
+    if guard.yes {
+        return Ok(());
+    }
+    if guard.headless {
+        return Err(CliError::new(
+            ErrorKind::Usage,
+            format!("Pass --yes to delete the {} '{}' in headless mode.", impact.kind, impact.name)
+        ).into());
+    }
+    if !typed(impact)? {
+        return Err(CliError::new(
+            ErrorKind::Usage,
+            format!("The name didn't match '{}', nothing was deleted.", impact.name)
+        ).into());
+    }
+
+    Ok(())
+}
+
+/// Ask for the name to be typed back.
+pub fn typed(impact: &Impact) -> Result<bool> {
+    let input: String = Input::new()
+        .with_prompt(format!("Type the {} name '{}' to confirm", impact.kind, impact.name))
+        .allow_empty(true)
+        .interact()?;
+
+    Ok(input.trim() == impact.name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn impact(production: bool) -> Impact {
+        Impact { production, ..Impact::new("install", "acmeprod") }
+    }
+
+    fn guard(yes: bool, force_production: bool, headless: bool) -> Guard {
+        Guard { yes, force_production, headless }
+    }
+
+    fn refusal(impact: &Impact, guard: &Guard) -> String {
+        let err = confirm(impact, guard).err().unwrap();
+        assert_eq!(CliError::kind_of(&err), ErrorKind::Usage);
+        err.to_string()
+    }
+
+    #[test]
+    fn production_needs_force_even_with_yes() {
+        for guard in [guard(true, false, true), guard(true, false, false), guard(false, false, true)] {
+            assert!(refusal(&impact(true), &guard).contains("pass --force-production"));
+        }
+        assert!(confirm(&impact(true), &guard(true, true, true)).is_ok());
+    }
+
+    #[test]
+    fn yes_skips_the_prompt() {
+        assert!(confirm(&impact(false), &guard(true, false, true)).is_ok());
+        assert!(confirm(&impact(false), &guard(true, false, false)).is_ok());
+    }
+
+    #[test]
+    fn headless_runs_need_yes() {
+        assert_eq!(refusal(&impact(false), &guard(false, false, true)), "Pass --yes to delete the install 'acmeprod' in headless mode.");
+        // Forcing production doesn't stand in for --yes.
+        assert!(refusal(&impact(true), &guard(false, true, true)).starts_with("Pass --yes"));
+    }
+}
@@ -0,0 +1,496 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+    sync::{atomic::{AtomicUsize, Ordering}, Mutex},
+    thread
+};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use console::style;
+use dialoguer::Confirm;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use wpe::*;
+use crate::output::{Output, Kind};
+use crate::cli::Environment;
+
+/// Version of the results file layout.
+const RESULTS_VERSION: u32 = 1;
+
+/// A row of the import file. Rows with the same site and account share the site,
+/// and a row without an install only creates the site.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Row {
+    pub site: String,
+    pub account: String,
+    #[serde(default)]
+    pub install: Option<String>,
+    #[serde(default)]
+    pub environment: Option<String>
+}
+
+/// What became of a row, as kept in the results file.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Outcome {
+    #[serde(flatten)]
+    pub row: Row,
+    pub status: Status,
+    #[serde(default)]
+    pub site_id: Option<String>,
+    #[serde(default)]
+    pub install_id: Option<String>,
+    #[serde(default)]
+    pub error: Option<String>
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Created,
+    Failed
+}
+
+/// The results file, read back on a rerun to skip the rows that were created.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Results {
+    pub version: u32,
+    pub updated_at: DateTime<Utc>,
+    pub rows: Vec<Outcome>
+}
+
+impl Results {
+    fn load(path: &Path) -> Result<Vec<Outcome>> {
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let text = fs::read_to_string(path).with_context(|| format!("Unable to read {}", path.display()))?;
+        let results: Self = serde_json::from_str(&text).with_context(|| format!("Unable to parse {}", path.display()))?;
+        Ok(results.rows)
+    }
+
+    fn save(path: &Path, rows: &[Outcome]) -> Result<()> {
+        let results = Self { version: RESULTS_VERSION, updated_at: Utc::now(), rows: rows.to_vec() };
+        fs::write(path, serde_json::to_string_pretty(&results)?)
+            .with_context(|| format!("Unable to write {}", path.display()))
+    }
+}
+
+/// Rows of the same site, created one after the other once the site exists.
+struct Group {
+    account_id: String,
+    site: String,
+    /// The site's ID when it exists already.
+    site_id: Option<String>,
+    rows: Vec<usize>
+}
+
+/// Read the rows of a CSV, YAML or JSON file.
+fn read(file: &str) -> Result<Vec<Row>> {
+    let path = Path::new(file);
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+    let invalid = |e: String| CliError::new(ErrorKind::Validation, format!("Unable to parse {}: {}.", file, e));
+
+    match extension {
+        "csv" => csv::Reader::from_path(path)
+            .map_err(|e| invalid(e.to_string()))?
+            .deserialize()
+            .collect::<Result<Vec<Row>, _>>()
+            .map_err(|e| invalid(e.to_string()).into()),
+        "yaml" | "yml" | "json" => {
+            let text = fs::read_to_string(path).with_context(|| format!("Unable to read {}", file))?;
+            serde_yaml::from_str(&text).map_err(|e| invalid(e.to_string()).into())
+        },
+        _ => Err(CliError::new(ErrorKind::Usage, format!("Unable to import {}, use a .csv, .yaml or .json file.", file)).into())
+    }
+}
+
+/// Check every row before anything is created. Returns the account ID of each row.
+/**
+  - Accounts have to resolve and sites need a name.
+  - Installs need a valid name that isn't taken, and an environment.
+  - Rows created by an earlier run are left out of the checks.
+  */
+fn validate(api: &API, rows: &[Row], done: &[bool]) -> Result<Vec<String>> {
+    let taken: HashSet<String> = api
+        .get_all_installs(None)?
+        .iter()
+        .filter_map(|i| i["name"].as_str().map(String::from))
+        .collect();
+
+    let mut accounts: HashMap<&str, Result<String, String>> = HashMap::new();
+    for row in rows {
+        accounts
+            .entry(row.account.as_str())
+            .or_insert_with(|| api.resolve(Resource::Account, &row.account).map_err(|e| e.to_string()));
+    }
+
+    check(rows, done, &taken, &accounts)
+}
+
+/// The checks of `validate`, given the installs that exist and what each account resolved to.
+fn check(
+    rows: &[Row],
+    done: &[bool],
+    taken: &HashSet<String>,
+    accounts: &HashMap<&str, Result<String, String>>
+) -> Result<Vec<String>> {
+    let mut seen = HashSet::new();
+    let mut errors = Vec::new();
+    let mut account_ids = Vec::new();
+
+    for (i, row) in rows.iter().enumerate() {
+        let mut error = |message: String| errors.push(format!("row {}: {}", i + 1, message));
+
+        match &accounts[row.account.as_str()] {
+            Ok(id) => account_ids.push(id.clone()),
+            Err(e) => {
+                error(e.clone());
+                account_ids.push(String::new());
+            }
+        }
+
+        if row.site.trim().is_empty() {
+            error("the site needs a name.".to_string());
+        }
+
+        match (&row.install, &row.environment) {
+            (Some(install), environment) => {
+                if !is_install_name(install) {
+                    error(format!("'{}' isn't a valid install name, use 3 to 14 lowercase letters and digits starting with a letter.", install));
+                }
+                if !seen.insert(install.as_str()) {
+                    error(format!("the install '{}' is in the file more than once.", install));
+                }
+                if !done[i] && taken.contains(install) {
+                    error(format!("an install named '{}' exists already.", install));
+                }
+                match environment {
+                    Some(environment) if Environment::from_str(environment, true).is_err() => {
+                        error(format!("'{}' isn't an environment, use production, staging or development.", environment));
+                    },
+                    Some(_) => {},
+                    None => error(format!("the install '{}' needs an environment.", install))
+                }
+            },
+            (None, Some(_)) => error("an environment was given without an install.".to_string()),
+            (None, None) => {}
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(CliError::new(
+            ErrorKind::Validation,
+            format!("{} problem(s) found, nothing was created:\n  {}", errors.len(), errors.join("\n  "))
+        ).into());
+    }
+
+    Ok(account_ids)
+}
+
+/// The outcome of each row that an earlier run created, `None` for the rows still to do.
+fn carried_over(rows: &[Row], previous: &[Outcome]) -> Vec<Option<Outcome>> {
+    rows.iter()
+        .map(|row| previous.iter().find(|o| o.row == *row && o.status == Status::Created).cloned())
+        .collect()
+}
+
+/// Group the rows still to do by account and site, in the order they first appear.
+/// `sites` holds the IDs of the sites that exist by account ID and name.
+fn group(rows: &[Row], done: &[bool], account_ids: &[String], sites: &HashMap<(String, String), String>) -> Vec<Group> {
+    let mut groups: Vec<Group> = Vec::new();
+    for (i, row) in rows.iter().enumerate().filter(|(i, _)| !done[*i]) {
+        let key = (account_ids[i].clone(), row.site.clone());
+        match groups.iter_mut().find(|g| g.account_id == key.0 && g.site == key.1) {
+            Some(group) => group.rows.push(i),
+            None => groups.push(Group { site_id: sites.get(&key).cloned(), account_id: key.0, site: key.1, rows: vec![i] })
+        }
+    }
+    groups
+}
+
+/// Create a group's site unless it exists, then each of its installs.
+fn create(api: &API, group: &Group, rows: &[Row]) -> Vec<(usize, Outcome)> {
+    let outcome = |i: usize, site_id: Option<String>, result: Result<Option<String>>| {
+        let (status, install_id, error) = match result {
+            Ok(install_id) => (Status::Created, install_id, None),
+            Err(e) => (Status::Failed, None, Some(format!("{:#}", e)))
+        };
+        (i, Outcome { row: rows[i].clone(), status, site_id, install_id, error })
+    };
+
+    let site_id = match &group.site_id {
+        Some(id) => Ok(id.clone()),
+        None => api
+            .add_site(&Site { name: group.site.clone(), account_id: group.account_id.clone() })
+            .and_then(|site| match site["id"].as_str() {
+                Some(id) => Ok(id.to_string()),
+                // A dry run sends nothing, so there's no ID to show.
+                None if api.is_dry_run() => Ok("<new site>".to_string()),
+                // Installs can't be added without it, and a rerun must not skip them.
+                None => Err(anyhow!("The site '{}' was added but the API returned no ID.", group.site))
+            })
+    };
+    let site_id = match site_id {
+        Ok(id) => id,
+        Err(e) => {
+            let error = format!("{:#}", e);
+            return group.rows.iter().map(|i| outcome(*i, None, Err(anyhow!(error.clone())))).collect();
+        }
+    };
+
+    group.rows
+        .iter()
+        .map(|i| {
+            let row = &rows[*i];
+            let result = match (&row.install, &row.environment) {
+                (Some(install), Some(environment)) => api
+                    .add_install(&Install {
+                        name: install.clone(),
+                        account_id: group.account_id.clone(),
+                        site_id: site_id.clone(),
+                        environment: environment.to_lowercase()
+                    })
+                    .map(|created| created["id"].as_str().map(String::from)),
+                _ => Ok(None)
+            };
+            outcome(*i, Some(site_id.clone()), result)
+        })
+        .collect()
+}
+
+/// Provides logic for the import command.
+///
+/// # Arguments
+///
+/// * `file` - &str
+/// * `results` - Option<&str>
+/// * `concurrency` - u8
+/// * `yes` - bool
+/// * `api` - &API
+/// * `headless` - bool
+/// * `out` - &Output
+pub fn init(
+    file: &str,
+    results: Option<&str>,
+    concurrency: u8,
+    yes: bool,
+    api: &API,
+    headless: bool,
+    out: &Output
+) -> Result<()> {
+    let rows = read(file)?;
+    if rows.is_empty() {
+        return Err(CliError::new(ErrorKind::Validation, format!("{} has no rows.", file)).into());
+    }
+    let results_path = match results {
+        Some(path) => Path::new(path).to_path_buf(),
+        None => Path::new(file).with_extension("results.json")
+    };
+
+    // Rows created by an earlier run keep their outcome and are skipped.
+    let mut outcomes = carried_over(&rows, &Results::load(&results_path)?);
+    let done: Vec<bool> = outcomes.iter().map(Option::is_some).collect();
+
+    let account_ids = validate(api, &rows, &done)?;
+
+    // Sites that exist, whether from an earlier run or not, get the new installs.
+    let mut sites: HashMap<(String, String), String> = HashMap::new();
+    for site in api.get_all_sites(None)? {
+        if let (Some(account), Some(name), Some(id)) = (site["account"]["id"].as_str(), site["name"].as_str(), site["id"].as_str()) {
+            sites.entry((account.to_string(), name.to_string())).or_insert_with(|| id.to_string());
+        }
+    }
+
+    let groups = group(&rows, &done, &account_ids, &sites);
+    let skipped = done.iter().filter(|d| **d).count();
+    if groups.is_empty() {
+        eprintln!("All {} rows were imported already, see {}.", rows.len(), results_path.display());
+        return Ok(());
+    }
+
+    let new_sites = groups.iter().filter(|g| g.site_id.is_none()).count();
+    let installs = groups.iter().flat_map(|g| &g.rows).filter(|i| rows[**i].install.is_some()).count();
+    eprintln!(
+        "{} {} new site(s) and {} install(s) from {}, skipping {} row(s) imported before.",
+        style("Creating").bold(),
+        new_sites,
+        installs,
+        file,
+        skipped
+    );
+    if !yes && !headless && !Confirm::new().with_prompt("Continue?").default(false).interact()? {
+        println!("Cancelling.");
+        return Ok(());
+    }
+
+    // Sites are created in parallel, and the results file is written as each one finishes
+    // so an interrupted run can pick up where it stopped.
+    let next = AtomicUsize::new(0);
+    let state = Mutex::new(&mut outcomes);
+    let workers = usize::from(concurrency).min(groups.len());
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers).map(|_| {
+            scope.spawn(|| -> Result<()> {
+                while let Some(group) = groups.get(next.fetch_add(1, Ordering::SeqCst)) {
+                    let created = create(api, group, &rows);
+
+                    let mut outcomes = state.lock().map_err(|_| anyhow!("An import worker panicked."))?;
+                    for (i, outcome) in created {
+                        let label = format!("{} / {}", outcome.row.site, outcome.row.install.as_deref().unwrap_or("-"));
+                        match &outcome.error {
+                            None => eprintln!("{} {}", style("created").green(), label),
+                            Some(e) => eprintln!("{} {}: {}", style("failed").red(), label, e)
+                        }
+                        outcomes[i] = Some(outcome);
+                    }
+                    if !api.is_dry_run() {
+                        Results::save(&results_path, &outcomes.iter().flatten().cloned().collect::<Vec<_>>())?;
+                    }
+                }
+                Ok(())
+            })
+        }).collect();
+
+        handles
+            .into_iter()
+            .try_for_each(|handle| handle.join().map_err(|_| anyhow!("An import worker panicked."))?)
+    })?;
+
+    let report: Vec<Value> = outcomes
+        .iter()
+        .enumerate()
+        .filter_map(|(i, o)| o.as_ref().map(|o| (i, o)))
+        .map(|(i, outcome)| json!({
+            "row": i + 1,
+            "site": outcome.row.site,
+            "install": outcome.row.install,
+            "result": match (done[i], outcome.status) {
+                (true, _) => "skipped",
+                (false, Status::Created) => "created",
+                (false, Status::Failed) => "failed"
+            },
+            "site_id": outcome.site_id,
+            "install_id": outcome.install_id,
+            "detail": outcome.error.clone().unwrap_or_default()
+        }))
+        .collect();
+    out.print(&report, Kind::Other)?;
+
+    let failed = outcomes.iter().flatten().filter(|o| o.status == Status::Failed).count();
+    if !api.is_dry_run() {
+        eprintln!("Results written to {}.", results_path.display());
+    }
+    if failed > 0 {
+        return Err(anyhow!("{} of {} rows failed, run the import again to retry them.", failed, rows.len()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(site: &str, install: Option<&str>, environment: Option<&str>) -> Row {
+        Row {
+            site: site.to_string(),
+            account: "acme".to_string(),
+            install: install.map(String::from),
+            environment: environment.map(String::from)
+        }
+    }
+
+    fn checked(rows: &[Row], done: &[bool], taken: &[&str]) -> Result<Vec<String>> {
+        let taken = taken.iter().map(|t| t.to_string()).collect();
+        let accounts = HashMap::from([("acme", Ok("a1".to_string()))]);
+        check(rows, done, &taken, &accounts)
+    }
+
+    fn problems(rows: &[Row], done: &[bool], taken: &[&str]) -> String {
+        checked(rows, done, taken).err().map(|e| e.to_string()).unwrap_or_default()
+    }
+
+    #[test]
+    fn accepts_valid_rows() {
+        let rows = [row("Acme", Some("acmeprod"), Some("Production")), row("Blog", None, None)];
+        assert_eq!(checked(&rows, &[false, false], &[]).unwrap(), vec!["a1", "a1"]);
+    }
+
+    #[test]
+    fn rejects_installs_named_twice() {
+        let rows = [row("Acme", Some("acmeprod"), Some("production")), row("Other", Some("acmeprod"), Some("staging"))];
+        let problems = problems(&rows, &[false, false], &[]);
+        assert!(problems.contains("row 2: the install 'acmeprod' is in the file more than once."), "{}", problems);
+        assert!(!problems.contains("row 1"));
+    }
+
+    #[test]
+    fn rejects_bad_environments_and_missing_ones() {
+        let rows = [row("Acme", Some("acmeprod"), Some("live")), row("Acme", Some("acmestg"), None), row("Acme", None, Some("staging"))];
+        let problems = problems(&rows, &[false, false, false], &[]);
+        assert!(problems.starts_with("3 problem(s) found"), "{}", problems);
+        assert!(problems.contains("row 1: 'live' isn't an environment"));
+        assert!(problems.contains("row 2: the install 'acmestg' needs an environment."));
+        assert!(problems.contains("row 3: an environment was given without an install."));
+    }
+
+    #[test]
+    fn rows_done_before_are_not_taken_installs() {
+        let rows = [row("Acme", Some("acmeprod"), Some("production")), row("Acme", Some("acmestg"), Some("staging"))];
+        // acmeprod exists because the earlier run created it, acmestg exists for another reason.
+        let problems = problems(&rows, &[true, false], &["acmeprod", "acmestg"]);
+        assert!(!problems.contains("row 1"), "{}", problems);
+        assert!(problems.contains("row 2: an install named 'acmestg' exists already."));
+    }
+
+    #[test]
+    fn reports_accounts_that_do_not_resolve() {
+        let rows = [Row { account: "nobody".to_string(), ..row("Acme", None, None) }];
+        let accounts = HashMap::from([("nobody", Err("No account named 'nobody' was found.".to_string()))]);
+        let err = check(&rows, &[false], &HashSet::new(), &accounts).err().unwrap();
+        assert!(err.to_string().contains("row 1: No account named 'nobody' was found."));
+    }
+
+    #[test]
+    fn rows_of_one_site_become_one_group() {
+        let rows = [
+            row("Acme", Some("acmeprod"), Some("production")),
+            row("Blog", Some("blogprod"), Some("production")),
+            row("Acme", Some("acmestg"), Some("staging"))
+        ];
+        let account_ids = vec!["a1".to_string(); 3];
+        let sites = HashMap::from([(("a1".to_string(), "Blog".to_string()), "s2".to_string())]);
+        let groups = group(&rows, &[false, false, false], &account_ids, &sites);
+
+        let summary: Vec<(&str, Option<&str>, &[usize])> = groups
+            .iter()
+            .map(|g| (g.site.as_str(), g.site_id.as_deref(), g.rows.as_slice()))
+            .collect();
+        assert_eq!(summary, vec![("Acme", None, &[0, 2][..]), ("Blog", Some("s2"), &[1][..])]);
+    }
+
+    #[test]
+    fn rows_created_before_are_skipped() {
+        let rows = [row("Acme", Some("acmeprod"), Some("production")), row("Acme", Some("acmestg"), Some("staging"))];
+        let outcome = |row: &Row, status: Status| Outcome {
+            row: row.clone(),
+            status,
+            site_id: Some("s1".to_string()),
+            install_id: None,
+            error: None
+        };
+        // Failed rows are retried.
+        let previous = [outcome(&rows[0], Status::Created), outcome(&rows[1], Status::Failed)];
+
+        let outcomes = carried_over(&rows, &previous);
+        assert!(outcomes[0].is_some() && outcomes[1].is_none());
+
+        let done: Vec<bool> = outcomes.iter().map(Option::is_some).collect();
+        let groups = group(&rows, &done, &["a1".to_string(), "a1".to_string()], &HashMap::new());
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].rows, vec![1]);
+    }
+}
@@ -0,0 +1,176 @@
+use std::{fs, io::Read, path::Path};
+use anyhow::{Context, Result};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::Value;
+use wpe::*;
+
+/// An update body along with the resource it applies to. Without an `id` the
+/// resource given on the command line is updated.
+#[derive(Deserialize, Debug)]
+pub struct Update<T> {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub patch: T
+}
+
+impl<T> Update<T> {
+    /// A body built from flags, applied to the resource on the command line.
+    pub fn new(patch: T) -> Self {
+        Self { id: None, patch }
+    }
+
+    /// The resource this body updates.
+    pub fn target<'a>(&'a self, given: Option<&'a str>) -> Result<&'a str> {
+        self.id.as_deref().or(given).ok_or_else(|| {
+            CliError::new(ErrorKind::Validation, "Each body needs an \"id\" when no resource is given.").into()
+        })
+    }
+}
+
+/// The request bodies of an add or update command, from `--file` or its flags.
+pub struct Bodies<T> {
+    pub items: Vec<T>,
+    /// Whether the file held an array, its responses are printed as one too.
+    many: bool
+}
+
+impl<T: DeserializeOwned> Bodies<T> {
+    /// Read the bodies from `file` when it is given, otherwise build one from the flags.
+    pub fn from(file: &Option<String>, flags: impl FnOnce() -> Result<T>) -> Result<Self> {
+        match file {
+            Some(path) => Self::read(path),
+            None => Ok(Self { items: vec![flags()?], many: false })
+        }
+    }
+
+    /// Read a JSON or YAML body, or an array of them, from a file or stdin with `-`.
+    pub fn read(path: &str) -> Result<Self> {
+        let text = text(path)?;
+        let source = if path == "-" { "stdin" } else { path };
+        let value = parse(path, source, &text)?;
+        let many = value.is_array();
+        let values = match value {
+            Value::Array(values) => values,
+            value => vec![value]
+        };
+
+        let items = values
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| {
+                serde_json::from_value(value).map_err(|e| {
+                    let body = if many { format!("body {}", i + 1) } else { "body".to_string() };
+                    CliError::new(ErrorKind::Validation, format!("Invalid {} in {}: {}.", body, source, e)).into()
+                })
+            })
+            .collect::<Result<Vec<T>>>()?;
+
+        Ok(Self { items, many })
+    }
+}
+
+impl<T> Bodies<T> {
+    /// The responses to print, one value for a single body or a list for an array.
+    /// Nothing is printed when nothing was sent, e.g. with `--dry-run`.
+    pub fn output(&self, results: Vec<Value>) -> Value {
+        let mut results: Vec<Value> = results.into_iter().filter(|r| !r.is_null()).collect();
+
+        match (self.many, results.len()) {
+            (_, 0) => Value::Null,
+            (false, 1) => results.remove(0),
+            _ => Value::Array(results)
+        }
+    }
+}
+
+/// Read a file, or stdin with `-`.
+pub fn text(path: &str) -> Result<String> {
+    if path == "-" {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text)?;
+        return Ok(text);
+    }
+
+    fs::read_to_string(path).with_context(|| format!("Unable to read {}", path))
+}
+
+/// Parse JSON or YAML, going by the extension of the file. Stdin can be either.
+fn parse(path: &str, source: &str, text: &str) -> Result<Value> {
+    let extension = Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or_default();
+    let invalid = |e: String| CliError::new(ErrorKind::Validation, format!("Unable to parse {}: {}.", source, e));
+
+    match extension {
+        "json" => serde_json::from_str(text).map_err(|e| invalid(e.to_string()).into()),
+        "yaml" | "yml" => serde_yaml::from_str(text).map_err(|e| invalid(e.to_string()).into()),
+        _ => serde_json::from_str(text)
+            .or_else(|_| serde_yaml::from_str(text))
+            .map_err(|e| invalid(e.to_string()).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use serde_json::json;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Patch {
+        name: String
+    }
+
+    /// Read bodies from a temporary file with the given extension.
+    fn read<T: DeserializeOwned>(suffix: &str, text: &str) -> Result<Bodies<T>> {
+        let mut file = tempfile::Builder::new().suffix(suffix).tempfile().unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+        Bodies::read(file.path().to_str().unwrap())
+    }
+
+    #[test]
+    fn reads_a_single_body_or_an_array() {
+        let single: Bodies<Patch> = read(".json", r#"{ "name": "acme" }"#).unwrap();
+        assert_eq!(single.items, vec![Patch { name: "acme".to_string() }]);
+        assert_eq!(single.output(vec![json!(1)]), json!(1));
+
+        let many: Bodies<Patch> = read(".json", r#"[{ "name": "a" }, { "name": "b" }]"#).unwrap();
+        assert_eq!(many.items.len(), 2);
+        assert_eq!(many.output(vec![json!(1), Value::Null]), json!([1]));
+    }
+
+    #[test]
+    fn reads_yaml_by_extension_or_content() {
+        let yaml: Bodies<Patch> = read(".yaml", "- name: a\n- name: b\n").unwrap();
+        assert_eq!(yaml.items[1], Patch { name: "b".to_string() });
+
+        let unknown: Bodies<Patch> = read(".txt", "name: acme\n").unwrap();
+        assert_eq!(unknown.items[0].name, "acme");
+
+        // JSON is YAML too, but a .json file is only read as JSON.
+        assert!(read::<Patch>(".json", "name: acme\n").is_err());
+    }
+
+    #[test]
+    fn body_ids_win_over_the_resource_given() {
+        let bodies: Bodies<Update<Patch>> = read(".yaml", "- id: acmestg\n  name: a\n- name: b\n").unwrap();
+
+        assert_eq!(bodies.items[0].target(Some("acmeprod")).unwrap(), "acmestg");
+        assert_eq!(bodies.items[1].target(Some("acmeprod")).unwrap(), "acmeprod");
+        assert_eq!(bodies.items[0].patch, Patch { name: "a".to_string() });
+
+        let err = bodies.items[1].target(None).err().unwrap();
+        assert_eq!(CliError::kind_of(&err), ErrorKind::Validation);
+        assert_eq!(err.to_string(), "Each body needs an \"id\" when no resource is given.");
+    }
+
+    #[test]
+    fn reports_malformed_files_and_bodies() {
+        let err = read::<Patch>(".json", "{ \"name\": ").err().unwrap();
+        assert_eq!(CliError::kind_of(&err), ErrorKind::Validation);
+        assert!(err.to_string().contains("Unable to parse"), "{}", err);
+
+        let err = read::<Patch>(".json", r#"[{ "name": "a" }, { "title": "b" }]"#).err().unwrap();
+        assert!(err.to_string().starts_with("Invalid body 2 in "), "{}", err);
+        assert!(err.to_string().contains("missing field `name`"), "{}", err);
+    }
+}
@@ -1,205 +1,131 @@
-use dialoguer::{
-    Select,
-    Input,
-    Confirm,
-    theme::ColorfulTheme
-};
-use clap::ArgMatches;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use wpe::*;
 use serde_json::Value;
+use crate::output::{Output, Kind};
+use crate::filter::Criteria;
+use crate::cli::{InstallsArgs, InstallCommand};
+use crate::session::{Session, Screen};
+use crate::guard::{self, Guard, Impact};
+use crate::diff;
+use crate::input::{Bodies, Update};
+
+/// Update an install once its diff is reviewed. Returns null when nothing was sent.
+fn update(api: &API, id: &str, data: &InstallPatch, headless: bool) -> Result<Value> {
+    let current = api.get_install_by_id(id)?;
+    let what = format!("install '{}'", current["name"].as_str().unwrap_or(id));
+    if !diff::review(&what, &diff::changes(&current, data, diff::INSTALL_FIELDS)?, headless)? {
+        return Ok(Value::Null);
+    }
 
-const ENV: [&str; 3] = ["development", "staging", "production"];
-
-fn get_install_data(results: &Vec<Value>, api: &API) -> Result<(String, String)>{
-
-    let site_selection = get_selections!(results, "Select a site to update", "name");
-    let site_id = results[site_selection]["id"].as_str().unwrap();
-    let selected_site = api.get_site_by_id(&site_id)?;
-
-    let installs = selected_site["installs"].as_array().unwrap();
-    let install_selection = get_selections!(installs, "Select an Install", "name");
-    let install = installs[install_selection]["id"].as_str().unwrap();
-
-    return Ok((site_id.to_string(), install.to_string()));
+    api.update_install(id, data)
 }
 
-/// Provides logic for the sites command.
+/// Provides logic for the installs command.
 ///
 /// # Arguments
 ///
-/// * `sub_n` - &ArgMatches
-/// * `command` - API
-/// * `headless` - Option<&bool>
-pub fn init(sub_n: &ArgMatches, api: API, headless: Option<&bool>) -> Result<()> {
-    let page = sub_n.get_one::<String>("PAGE");
-    let page_num: u8;
-
-    match page {
-        Some(x) => {
-            page_num = x.parse::<u8>().unwrap();
+/// * `args` - &InstallsArgs
+/// * `api` - &API
+/// * `headless` - bool
+/// * `out` - &Output
+/// * `account` - Option<&str>
+pub fn init(
+    args: &InstallsArgs,
+    api: &API,
+    headless: bool,
+    out: &Output,
+    account: Option<&str>
+) -> Result<()> {
+    let criteria = &args.criteria;
+
+    match &args.command {
+        Some(InstallCommand::Get { install }) => {
+            let id = api.resolve(Resource::Install, install)?;
+            let install = api.get_install_by_id(&id)?;
+            out.print(&install, Kind::Installs)?;
         },
-        None => {
-            page_num = 0;
-        }
-    }
-
-    // Fetch sites and display results. Will also show paginated results.
-    let next = api.get_sites(Some(page_num))?;
-    let results = next["results"].as_array().unwrap();
-
-    // Check for headless mode.
-    if let Some(true) = headless {
-        match sub_n.subcommand() {
-            Some(("list", sub)) => {
-                if let Some(id) = sub.get_one::<String>("ID") {
-                    let install = api.get_install_by_id(id)?;
-                    println!("{}", serde_json::to_string_pretty(&install)?);
-
-                } else {
-                    println!("{}", serde_json::to_string_pretty(results)?);
-                }
-            },
-            Some(("add", sub)) => {
-                let name = sub.get_one::<String>("NAME").unwrap();
-                let account_id = sub.get_one::<String>("ACCOUNT").unwrap();
-                let site_id = sub.get_one::<String>("SITE").unwrap();
-                let env = sub.get_one::<String>("ENV").unwrap();
-
-                let data = Install {
-                    name: name.to_string(),
-                    account_id: account_id.to_string(),
-                    site_id: site_id.to_string(),
-                    environment: env.to_string()
+        Some(InstallCommand::Add { name, site, environment, body }) => {
+            let bodies = Bodies::from(&body.file, || Ok(Install {
+                name: name.clone().unwrap_or_default(),
+                account_id: String::new(),
+                site_id: site.clone().unwrap_or_default(),
+                environment: environment.map(|e| e.as_str().to_string()).unwrap_or_default()
+            }))?;
+
+            let mut add_install = Vec::new();
+            for data in &bodies.items {
+                let site_id = api.resolve(Resource::Site, &data.site_id)?;
+                // New installs belong to the site's account unless one is given.
+                let account_id = match (data.account_id.as_str(), account) {
+                    ("", Some(account)) => account.to_string(),
+                    ("", None) => api.get_site_by_id(&site_id)?["account"]["id"]
+                        .as_str()
+                        .ok_or_else(|| anyhow!("Unable to find the account of site {}.", site_id))?
+                        .to_string(),
+                    (account_id, _) => account_id.to_string()
                 };
 
-                let add_install = api.add_install(&data)?;
-
-                println!("{}", serde_json::to_string_pretty(&add_install)?);
-            },
-            Some(("update", sub)) => {
-                let install_id = sub.get_one::<String>("ID").unwrap();
-                let site_id = sub.get_one::<String>("SITE").unwrap();
-                let env = sub.get_one::<String>("ENV").unwrap();
-
-                let data = InstallPatch {
-                    site_id: site_id.to_string(),
-                    environment: env.to_string()
-                };
-
-                let update_site = api.update_install(install_id, &data)?;
-
-                println!("{}", serde_json::to_string_pretty(&update_site)?);
-            },
-            Some(("delete", sub)) => {
-                let id = sub.get_one::<String>("ID").unwrap();
-
-                api.delete_install(id)?;
-            },
-            _ => {
-                println!("{}", serde_json::to_string_pretty(results)?);
+                add_install.push(api.add_install(&Install {
+                    name: data.name.clone(),
+                    account_id,
+                    site_id,
+                    environment: data.environment.clone()
+                })?);
             }
-        }
-    } else {
-        // Handle logic for when headless mode is not enabled
-        let options = vec!["List All", "Add Install", "Update Install", "Delete Install"];
-        let selection = Select::with_theme(&ColorfulTheme::default())
-            .with_prompt("Choose an option")
-            .items(&options)
-            .interact()?;
-
-        match selection {
-            0 => {
-                // Handle logic for listing sites.
-                let (_, install_id) = get_install_data(results, &api)?;
-                let install = api.get_install_by_id(install_id.as_str())?;
-
-                println!("Selection: {}", serde_json::to_string_pretty(&install)?);
-            },
-            1 => {
-                // Logic for adding an install to a site.
-                println!("Follow the prompts to add a install.");
-                let accounts_results = api.get_accounts(Some(0))?;
-                let accounts = accounts_results["results"].as_array().unwrap();
-
-                let account = get_selections!(accounts, "Select an account", "name");
-
-                let sites_results = api.get_sites(Some(1))?;
-                let sites = sites_results["results"].as_array().unwrap();
-
-                let site = get_selections!(sites, "Select a site", "name");
-
-                let install: String = Input::new()
-                    .with_prompt("Enter an install name")
-                    .interact()?;
-
-                let environment = Select::with_theme(&ColorfulTheme::default())
-                    .with_prompt("Select an environment")
-                    .items(&ENV)
-                    .interact()?;
-
-                let data = Install {
-                    name: install,
-                    account_id: accounts[account]["id"].as_str().unwrap().to_string(),
-                    site_id: sites[site]["id"].as_str().unwrap().to_string(),
-                    environment: ENV[environment].to_string()
-                };
-
-                let add_install= api.add_install(&data)?;
-
-                println!(
-                    "Successfully added install: {}",
-                    serde_json::to_string_pretty(&add_install)?
-                );
-            },
-            2 => {
-                // Logic for updating a site.
-                let (site_id, install) = get_install_data(results, &api)?;
-
-                let environment = Select::with_theme(&ColorfulTheme::default())
-                    .with_prompt("Select an environment")
-                    .items(&ENV)
-                    .interact()?;
-
-                let env = ENV[environment];
 
+            out.print(&bodies.output(add_install), Kind::Installs)?;
+        },
+        Some(InstallCommand::Update { install, site, environment, body }) => {
+            let bodies = Bodies::from(&body.file, || {
+                if site.is_none() && environment.is_none() {
+                    return Err(CliError::new(ErrorKind::Usage, "Nothing to update, pass --site or --environment.").into());
+                }
+                Ok(Update::new(InstallPatch {
+                    site_id: site.clone(),
+                    environment: environment.map(|e| e.as_str().to_string())
+                }))
+            })?;
+
+            let mut update_install = Vec::new();
+            for body in &bodies.items {
+                let install_id = api.resolve(Resource::Install, body.target(install.as_deref())?)?;
+                // The site can be a name in a file body as well as with --site, it is sent as its ID.
                 let data = InstallPatch {
-                    site_id: site_id.to_string(),
-                    environment: env.to_string()
+                    site_id: body.patch.site_id.as_ref().map(|s| api.resolve(Resource::Site, s)).transpose()?,
+                    environment: body.patch.environment.clone()
                 };
+                update_install.push(update(api, &install_id, &data, headless)?);
+            }
 
-                println!("{}", serde_json::to_string_pretty(&data)?);
-
-                if Confirm::new().with_prompt("Does this data look right?").interact()? {
-
-                    let update = api.update_install(&install, &data)?;
-                    println!(
-                        "Successfully updated install: {}",
-                        serde_json::to_string_pretty(&update)?
-                    );
-
-                } else {
-                    // Recursively call init to show prompts again.
-                    init(sub_n, api, headless)?;
-                }
-            },
-            3 => {
-                // Logic for deleting an install from a site.
-                let (_, install) = get_install_data(results, &api)?;
-
-                if Confirm::new().with_prompt("Does this data look right?").interact()? {
-
-                    api.delete_install(&install)?;
-                    println!("Install deleted!");
+            out.print(&bodies.output(update_install), Kind::Installs)?;
+        },
+        Some(InstallCommand::Delete { install, confirm }) => {
+            let id = api.resolve(Resource::Install, install)?;
+            let guard = Guard { yes: confirm.yes, force_production: confirm.force_production, headless };
+            guard::confirm(&Impact::install(api, &id)?, &guard)?;
 
-                } else {
-                    println!("Cancelling.");
-                }
-            },
-            _ => println!("An error occured with your selection")
+            api.delete_install(&id)?;
+        },
+        Some(InstallCommand::List) => list(api, args.page, criteria, out, account)?,
+        None if headless => list(api, args.page, criteria, out, account)?,
+        None => {
+            // The session keeps a stack of screens, so back and main menu work from anywhere.
+            Session::new(api, out, account)
+                .install_criteria(criteria.clone())
+                .force_production(args.force_production)
+                .run(Screen::Installs)?;
         }
     }
 
-    return Ok(());
+    Ok(())
 }
 
+/// Print a page of installs. Filters and sorting need every install, not just the requested page.
+fn list(api: &API, page: u32, criteria: &Criteria, out: &Output, account: Option<&str>) -> Result<()> {
+    if criteria.is_empty() {
+        let installs = api.get_installs(Some(page), account)?;
+        out.print(&installs["results"], Kind::Installs)
+    } else {
+        out.print(&criteria.apply(api.get_all_installs(account)?), Kind::Installs)
+    }
+}
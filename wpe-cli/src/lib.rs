@@ -2,17 +2,92 @@ use home_config::HomeConfig;
 use std::str;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use dialoguer::Input;
-use anyhow::Result;
+use dialoguer::{FuzzySelect, Input, theme::ColorfulTheme};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use std::fmt;
 
 
-#[derive(Serialize, Deserialize, Default, Debug)]
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
 pub struct Config {
     pub wpengine_user_id: String,
     pub wpengine_password: String,
     pub wpengine_api: String
 }
 
+/// The kinds of failure reported by `wpe`, each exiting with its own code.
+/**
+  - 1 other, 2 usage, 3 auth, 4 not found, 5 validation, 6 rate limit, 7 API, 8 network.
+  */
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Other,
+    Usage,
+    Auth,
+    NotFound,
+    Validation,
+    RateLimit,
+    Api,
+    Network
+}
+
+impl ErrorKind {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ErrorKind::Other => 1,
+            ErrorKind::Usage => 2,
+            ErrorKind::Auth => 3,
+            ErrorKind::NotFound => 4,
+            ErrorKind::Validation => 5,
+            ErrorKind::RateLimit => 6,
+            ErrorKind::Api => 7,
+            ErrorKind::Network => 8
+        }
+    }
+
+    /// The kind of an HTTP error status.
+    fn from_status(status: u16) -> Self {
+        match status {
+            401 | 403 => ErrorKind::Auth,
+            404 => ErrorKind::NotFound,
+            400 | 409 | 422 => ErrorKind::Validation,
+            429 => ErrorKind::RateLimit,
+            _ => ErrorKind::Api
+        }
+    }
+}
+
+/// An error of a known kind, with the HTTP status when it came from the API.
+#[derive(Debug)]
+pub struct CliError {
+    pub kind: ErrorKind,
+    pub status: Option<u16>,
+    pub message: String
+}
+
+impl CliError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self { kind, status: None, message: message.into() }
+    }
+
+    /// The kind of any error, `Other` unless a [`CliError`] is part of its chain.
+    pub fn kind_of(err: &anyhow::Error) -> ErrorKind {
+        err.chain()
+            .find_map(|e| e.downcast_ref::<CliError>())
+            .map(|e| e.kind)
+            .unwrap_or(ErrorKind::Other)
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CliError {}
+
 /// This function will prompt the user for their WPEngine API credentials
 /**
   - Stores wpengine API username and password in config file.
@@ -31,6 +106,19 @@ fn set_config(username: String, token: String) -> Result<()> {
     Ok(())
 }
 
+/// Check if a value looks like a wpengine UUID.
+pub fn is_uuid(value: &str) -> bool {
+    let re = Regex::new(r"^[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}$").unwrap();
+    re.is_match(value)
+}
+
+/// Check if a name can be used for a new install: 3 to 14 lowercase letters and
+/// digits, starting with a letter.
+pub fn is_install_name(value: &str) -> bool {
+    let re = Regex::new(r"^[a-z][a-z0-9]{2,13}$").unwrap();
+    re.is_match(value)
+}
+
 /// Check if username and password are stored in config file.
 fn authenticated() -> bool {
 
@@ -40,14 +128,9 @@ fn authenticated() -> bool {
     // Check if config file exists.
     if file.exists() {
         let toml = config.toml::<Config>().unwrap();
-        let re = Regex::new(r"^[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}$").unwrap();
         // check if username matches UUID format
         // need a better check here, should consider pinging the API for a 200.
-        if re.is_match(&toml.wpengine_user_id) {
-            true
-        } else {
-            false
-        }
+        is_uuid(&toml.wpengine_user_id)
     } else {
         false
     }
@@ -56,15 +139,17 @@ fn authenticated() -> bool {
 /// Get username and password from config file.
 pub fn get_config() -> Config {
     let config = HomeConfig::with_config_dir("wpe", "wpeconfig.toml");
-    let toml = config.toml::<Config>().unwrap();
-    toml
+    config.toml::<Config>().unwrap()
 }
 
 /// Reset the config file. This should be used if you change your API token or for debugging.
-pub fn reset() -> Result<()> {
+/// With `dry_run` the file that would be removed is only printed.
+pub fn reset(dry_run: bool) -> Result<()> {
     let config = HomeConfig::with_config_dir("wpe", "wpeconfig.toml");
     let file = HomeConfig::path(&config);
-    if file.exists() {
+    if dry_run {
+        eprintln!("DELETE {}", file.display());
+    } else if file.exists() {
         std::fs::remove_file(file)?;
     }
 
@@ -91,85 +176,131 @@ pub fn set_auth() -> Result<()> {
 }
 
 /// Handles user authentication.
-/// If the user is not authenticated redirect them to authentication.
-pub fn init() -> Result<()> {
+/// If the user is not authenticated redirect them to authentication,
+/// unless there is no one to prompt in headless mode.
+pub fn init(headless: bool) -> Result<()> {
     if !authenticated() {
+        if headless {
+            return Err(CliError::new(
+                ErrorKind::Auth,
+                "No API credentials found in $HOME/.config/wpe/wpeconfig.toml, run `wpe auth login` in a terminal first."
+            ).into());
+        }
         set_auth()?;
     }
     Ok(())
 }
 
+#[derive(Clone)]
 pub struct API {
     client: reqwest::blocking::Client,
     config: Config,
+    dry_run: bool
+}
+
+/// Body fields whose values are never printed, matched anywhere in the key.
+const SECRET_FIELDS: [&str; 4] = ["password", "token", "secret", "private_key"];
+
+/// Replace the values of secret fields, at any depth, with a placeholder.
+fn redact(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                let key = key.to_lowercase();
+                if SECRET_FIELDS.iter().any(|s| key.contains(s)) {
+                    *value = serde_json::Value::String("[REDACTED]".to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        },
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
 }
 
 #[derive(Serialize, Deserialize, Default, Debug)]
 pub struct Site {
     pub name: String,
+    /// Left out of a `--file` body, the account comes from `--account`.
+    #[serde(default)]
     pub account_id: String
 }
 
 #[derive(Serialize, Deserialize, Default, Debug)]
 pub struct SitePatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Install {
     pub name: String,
+    /// Left out of a `--file` body, the account comes from `--account` or the site.
+    #[serde(default)]
     pub account_id: String,
     pub site_id: String,
     pub environment: String
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Partial install update, only the fields that are set are sent.
+#[derive(Serialize, Deserialize, Default, Debug)]
 pub struct InstallPatch {
-    pub site_id: String,
-    pub environment: String
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub site_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>
 }
 
-#[derive(Serialize, Deserialize, Default, Debug)]
-struct User {
-    account_id: String,
-    first_name: String,
-    last_name: String,
-    email: String,
-    roles: String,
-    install_ids: Vec<String>
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct User {
+    /// Left out of a `--file` body, the account comes from `--account`.
+    #[serde(default)]
+    pub account_id: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub email: String,
+    pub roles: String,
+    #[serde(default)]
+    pub install_ids: Vec<String>
 }
 
-#[derive(Serialize, Deserialize, Default, Debug)]
-struct UserPatch {
-    roles: Option<String>,
-    install_ids: Option<Vec<String>>
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct UserPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roles: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub install_ids: Option<Vec<String>>
 }
 
 #[derive(Serialize, Deserialize, Default, Debug)]
 pub struct AccountUser {
-    user: User
+    pub user: User
 }
 
 #[derive(Serialize, Deserialize, Default, Debug)]
 pub struct AccountUserPatch {
-    user: UserPatch
+    pub user: UserPatch
 }
 
 #[derive(Serialize, Deserialize, Default, Debug)]
 pub struct Domain {
-    name: String,
-    primary: bool
+    pub name: String,
+    #[serde(default)]
+    pub primary: bool
 }
 
 #[derive(Serialize, Deserialize, Default, Debug)]
 pub struct DomainPatch {
-    primary: Option<bool>,
-    redirect_to: Option<String>
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub primary: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redirect_to: Option<String>
 }
 
 #[derive(Serialize, Deserialize, Default, Debug)]
 pub struct SSHKey {
-    public_key: String
+    pub public_key: String
 }
 
 #[derive(Serialize, Deserialize, Default, Debug)]
@@ -179,8 +310,181 @@ pub struct Cache {
 
 #[derive(Serialize, Deserialize, Default, Debug)]
 pub struct Backup {
-    description: String,
-    notification_emails: Vec<String>
+    pub description: String,
+    pub notification_emails: Vec<String>
+}
+
+/// A backup created through the CLI, as recorded in the local ledger.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BackupEntry {
+    pub id: String,
+    pub install_id: String,
+    pub description: String,
+    pub created_at: DateTime<Utc>,
+    pub status: String,
+    /// Why the last refresh couldn't get the status, cleared once one does.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>
+}
+
+impl BackupEntry {
+    /// Backups that have finished one way or another no longer need polling.
+    /// `not_found` is set when the backup or its install has gone.
+    pub fn is_pending(&self) -> bool {
+        !matches!(self.status.as_str(), "completed" | "failed" | "aborted" | "not_found")
+    }
+}
+
+/// Local record of every backup requested through the CLI.
+/**
+  - The API only returns a backup when asked for it by ID, so we keep the IDs.
+  - $HOME/.config/wpe/backups.json
+  */
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct BackupLedger {
+    pub backups: Vec<BackupEntry>
+}
+
+impl BackupLedger {
+    fn file() -> HomeConfig {
+        HomeConfig::with_config_dir("wpe", "backups.json")
+    }
+
+    /// Load the ledger, starting an empty one if nothing has been recorded yet.
+    pub fn load() -> Result<Self> {
+        let config = Self::file();
+        if !config.path().exists() {
+            return Ok(Self::default());
+        }
+        config.json::<Self>().map_err(|e| anyhow!("Unable to read backup ledger: {:?}", e))
+    }
+
+    /// Write the ledger back to disk.
+    pub fn save(&self) -> Result<()> {
+        Self::file()
+            .save_json(self)
+            .map_err(|e| anyhow!("Unable to write backup ledger: {:?}", e))
+    }
+
+    /// Entries for a single install, or all of them. Newest first.
+    pub fn list(&self, install_id: Option<&str>) -> Vec<&BackupEntry> {
+        let mut entries: Vec<&BackupEntry> = self.backups
+            .iter()
+            .filter(|b| install_id.is_none_or(|id| b.install_id == id))
+            .collect();
+        entries.sort_by_key(|b| std::cmp::Reverse(b.created_at));
+        entries
+    }
+}
+
+/// The kinds of resource that can be looked up by name.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    Account,
+    Site,
+    Install
+}
+
+impl Resource {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Resource::Account => "account",
+            Resource::Site => "site",
+            Resource::Install => "install"
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IndexEntry {
+    pub id: String,
+    pub name: String
+}
+
+/// How long the index is trusted before a lookup rebuilds it, in hours.
+pub const INDEX_MAX_AGE: i64 = 24;
+
+/// Local cache of resource names and IDs used to resolve human names.
+/**
+  - Rebuilt from the paginated listings whenever a name can't be found or it is older than `INDEX_MAX_AGE`.
+  - Rebuilt when a request for an ID it lists returns 404, e.g. after an install was deleted.
+  - $HOME/.config/wpe/index.json
+  */
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct ResourceIndex {
+    pub updated_at: Option<DateTime<Utc>>,
+    pub accounts: Vec<IndexEntry>,
+    pub sites: Vec<IndexEntry>,
+    pub installs: Vec<IndexEntry>
+}
+
+impl ResourceIndex {
+    fn file() -> HomeConfig {
+        HomeConfig::with_config_dir("wpe", "index.json")
+    }
+
+    /// Load the cached index, or an empty one if it hasn't been built yet.
+    pub fn load() -> Result<Self> {
+        let config = Self::file();
+        if !config.path().exists() {
+            return Ok(Self::default());
+        }
+        config.json::<Self>().map_err(|e| anyhow!("Unable to read resource index: {:?}", e))
+    }
+
+    /// Write the index back to disk.
+    pub fn save(&self) -> Result<()> {
+        Self::file()
+            .save_json(self)
+            .map_err(|e| anyhow!("Unable to write resource index: {:?}", e))
+    }
+
+    pub fn entries(&self, kind: Resource) -> &[IndexEntry] {
+        match kind {
+            Resource::Account => &self.accounts,
+            Resource::Site => &self.sites,
+            Resource::Install => &self.installs
+        }
+    }
+
+    /// Whether it was never built or is older than `INDEX_MAX_AGE`.
+    pub fn is_stale(&self) -> bool {
+        self.updated_at
+            .is_none_or(|updated_at| Utc::now() - updated_at > chrono::Duration::hours(INDEX_MAX_AGE))
+    }
+
+    /// Whether a request path names an ID the index lists.
+    pub fn lists_id_in(&self, path: &str) -> bool {
+        [Resource::Account, Resource::Site, Resource::Install]
+            .into_iter()
+            .flat_map(|kind| self.entries(kind))
+            .any(|entry| path.split('/').any(|part| part == entry.id))
+    }
+
+    /// Entries whose name or ID matches the input, ignoring case.
+    pub fn matches(&self, kind: Resource, input: &str) -> Vec<&IndexEntry> {
+        self.entries(kind)
+            .iter()
+            .filter(|e| e.name.eq_ignore_ascii_case(input) || e.id == input)
+            .collect()
+    }
+}
+
+/// Reduce a listing to the name and ID of each entry.
+fn index_entries(results: &[serde_json::Value]) -> Vec<IndexEntry> {
+    results
+        .iter()
+        .filter_map(|r| Some(IndexEntry {
+            id: r["id"].as_str()?.to_string(),
+            name: r["name"].as_str()?.to_string()
+        }))
+        .collect()
+}
+
+impl Default for API {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl API {
@@ -188,479 +492,704 @@ impl API {
     pub fn new() -> Self {
         let client = reqwest::blocking::Client::new();
         let config = get_config();
-        Self { client, config}
+        Self { client, config, dry_run: false }
     }
 
-    /// Status endpoint to check API health.
-    pub fn status(&self) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .get(&format!("{}/status", &self.config.wpengine_api))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .send()?
-            .json::<serde_json::Value>()?;
+    /// Print requests that would change anything instead of sending them.
+    /// Reads still go out, they're needed to resolve names and build the changes.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
 
-        Ok(res)
+    /// Whether requests that change anything are held back.
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
     }
 
-    pub fn swagger(&self) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .get(&format!("{}/swagger", &self.config.wpengine_api))
+    /// Print the method, URL and body of a request that a dry run holds back.
+    /// The preview goes to stderr, so stdout keeps only the command's own output, e.g. valid JSON.
+    fn print_dry_run(&self, request: &reqwest::blocking::Request) {
+        eprintln!("{} {}", request.method(), request.url());
+
+        let body = request
+            .body()
+            .and_then(|b| b.as_bytes())
+            .and_then(|b| serde_json::from_slice::<serde_json::Value>(b).ok());
+        if let Some(mut body) = body {
+            redact(&mut body);
+            eprintln!("{}", serde_json::to_string_pretty(&body).unwrap_or_default());
+        }
+    }
+
+    /// Authenticate and send a request, returning the JSON body.
+    /**
+      - Error statuses become a [`CliError`] with the API's message and the status' kind.
+      - Empty bodies, e.g. 202 and 204 responses, are returned as `null`.
+      - In a dry run anything but a GET is printed to stderr instead of sent, and returns `null`. Reads are still sent.
+      */
+    fn send(&self, request: reqwest::blocking::RequestBuilder) -> Result<serde_json::Value, anyhow::Error> {
+        let request = request
             .basic_auth(
                 &self.config.wpengine_user_id,
                 Some(&self.config.wpengine_password)
             )
-            .send()?
-            .json::<serde_json::Value>()?;
+            .build()
+            .map_err(|e| anyhow!("Invalid request: {}", e))?;
 
-        Ok(res)
-    }
+        if self.dry_run && request.method() != reqwest::Method::GET {
+            self.print_dry_run(&request);
+            return Ok(serde_json::Value::Null);
+        }
 
-    /// Get all sites from wpengine. Pass an optional page number to show more results.
-    pub fn get_sites(&self, page: Option<u8>) -> Result<serde_json::Value, anyhow::Error> {
+        let path = request.url().path().to_string();
         let res = self
             .client
-            .get(&format!("{}/sites?offset={}", &self.config.wpengine_api, page.unwrap_or(0) * 100))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .send()?
-            .json::<serde_json::Value>()?;
+            .execute(request)
+            .map_err(|e| CliError::new(ErrorKind::Network, format!("Unable to reach the API: {}", e)))?;
+
+        let status = res.status();
+        let retry_after = res
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from);
+        let text = res
+            .text()
+            .map_err(|e| CliError::new(ErrorKind::Network, format!("Unable to read the API response: {}", e)))?;
+        let body: serde_json::Value = if text.trim().is_empty() {
+            serde_json::Value::Null
+        } else {
+            serde_json::from_str(&text).map_err(|e| CliError {
+                kind: ErrorKind::Api,
+                status: Some(status.as_u16()),
+                message: format!("The API returned invalid JSON ({}): {}", status, e)
+            })?
+        };
+
+        if status.is_success() {
+            return Ok(body);
+        }
 
-        Ok(res)
+        // Validation errors list what was wrong with each field.
+        let mut message = body["message"].as_str().unwrap_or(status.canonical_reason().unwrap_or("Request failed")).to_string();
+        for error in body["errors"].as_array().into_iter().flatten() {
+            if let Some(detail) = error["message"].as_str() {
+                message.push_str(&format!("\n  {}", detail));
+            }
+        }
+        if let Some(seconds) = retry_after.filter(|_| status.as_u16() == 429) {
+            message.push_str(&format!(" (retry after {} seconds)", seconds));
+        }
+        // A name resolved to the ID of a deleted resource keeps failing until the index is rebuilt.
+        if status.as_u16() == 404 && ResourceIndex::load().is_ok_and(|i| i.lists_id_in(&path)) && self.refresh_index().is_ok() {
+            message.push_str(" (the name index was out of date and has been rebuilt, try again)");
+        }
+
+        Err(CliError {
+            kind: ErrorKind::from_status(status.as_u16()),
+            status: Some(status.as_u16()),
+            message: format!("{} {}", status.as_u16(), message)
+        }.into())
+    }
+
+    /// Status endpoint to check API health.
+    pub fn status(&self) -> Result<serde_json::Value, anyhow::Error> {
+        self.send(
+            self.client
+                .get(format!("{}/status", &self.config.wpengine_api))
+        )
+    }
+
+    pub fn swagger(&self) -> Result<serde_json::Value, anyhow::Error> {
+        self.send(
+            self.client
+                .get(format!("{}/swagger", &self.config.wpengine_api))
+        )
+    }
+
+    /// Walk every page of a listing endpoint and collect the results.
+    /// Sites and installs can be narrowed to a single account on the server.
+    fn paginate(&self, path: &str, account_id: Option<&str>)
+        -> Result<Vec<serde_json::Value>, anyhow::Error> {
+        let mut results = Vec::new();
+        let mut offset = 0;
+
+        loop {
+            let res = self.send(
+                self.client
+                    .get(format!("{}/{}?offset={}&limit=100", &self.config.wpengine_api, path, offset))
+                    .query(&[("account_id", account_id)])
+            )?;
+
+            let page = res["results"].as_array().cloned().unwrap_or_default();
+            let count = page.len();
+            results.extend(page);
+            offset += count;
+
+            if count == 0 || res["next"].is_null() {
+                break;
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Get every site from wpengine across all pages, optionally for a single account.
+    pub fn get_all_sites(&self, account_id: Option<&str>) -> Result<Vec<serde_json::Value>, anyhow::Error> {
+        self.paginate("sites", account_id)
+    }
+
+    /// Get every install from wpengine across all pages, optionally for a single account.
+    pub fn get_all_installs(&self, account_id: Option<&str>) -> Result<Vec<serde_json::Value>, anyhow::Error> {
+        self.paginate("installs", account_id)
+    }
+
+    /// Get every account from wpengine across all pages.
+    pub fn get_all_accounts(&self) -> Result<Vec<serde_json::Value>, anyhow::Error> {
+        self.paginate("accounts", None)
+    }
+
+    /// Get every domain of an install across all pages.
+    pub fn get_all_domains(&self, install_id: &str) -> Result<Vec<serde_json::Value>, anyhow::Error> {
+        self.paginate(&format!("installs/{}/domains", install_id), None)
+    }
+
+    /// Get every user of an account across all pages.
+    pub fn get_all_account_users(&self, account_id: &str) -> Result<Vec<serde_json::Value>, anyhow::Error> {
+        self.paginate(&format!("accounts/{}/account_users", account_id), None)
+    }
+
+    /// Get every ssh key of the authorized user across all pages.
+    pub fn get_all_ssh_keys(&self) -> Result<Vec<serde_json::Value>, anyhow::Error> {
+        self.paginate("ssh_keys", None)
+    }
+
+    /// Rebuild the local name index from the full listings.
+    pub fn refresh_index(&self) -> Result<ResourceIndex, anyhow::Error> {
+        let index = ResourceIndex {
+            updated_at: Some(Utc::now()),
+            accounts: index_entries(&self.get_all_accounts()?),
+            sites: index_entries(&self.get_all_sites(None)?),
+            installs: index_entries(&self.get_all_installs(None)?)
+        };
+        index.save()?;
+
+        Ok(index)
+    }
+
+    /// Resolve a UUID or human name to a resource ID.
+    /**
+      - UUIDs are passed through untouched.
+      - Names are looked up in the cached index, which is refreshed once on a miss or when it is stale.
+      - More than one match is an error listing the candidates.
+      */
+    pub fn resolve(&self, kind: Resource, input: &str) -> Result<String, anyhow::Error> {
+        if is_uuid(input) {
+            return Ok(input.to_string());
+        }
+
+        let mut index = ResourceIndex::load()?;
+        if index.is_stale() || index.matches(kind, input).is_empty() {
+            index = self.refresh_index()?;
+        }
+
+        let found = index.matches(kind, input);
+        match found.as_slice() {
+            [] => Err(CliError::new(ErrorKind::NotFound, format!("No {} named '{}' was found.", kind.label(), input)).into()),
+            [entry] => Ok(entry.id.clone()),
+            _ => {
+                let candidates = found
+                    .iter()
+                    .map(|e| format!("  {} ({})", e.name, e.id))
+                    .collect::<Vec<String>>()
+                    .join("\n");
+                Err(CliError::new(ErrorKind::Usage, format!(
+                    "'{}' matches more than one {}, use an ID instead:\n{}",
+                    input,
+                    kind.label(),
+                    candidates
+                )).into())
+            }
+        }
+    }
+
+    /// Get all sites from wpengine. Pass an optional page number to show more results
+    /// and an optional account ID to only list that account's sites.
+    pub fn get_sites(&self, page: Option<u32>, account_id: Option<&str>)
+        -> Result<serde_json::Value, anyhow::Error> {
+        self.send(
+            self.client
+                .get(format!("{}/sites?offset={}", &self.config.wpengine_api, u64::from(page.unwrap_or(0)) * 100))
+                .query(&[("account_id", account_id)])
+        )
     }
 
     /// Get a single site by its ID from the wpengine API
     pub fn get_site_by_id(&self, id: &str) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .get(&format!("{}/sites/{}", &self.config.wpengine_api,  id))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+        self.send(
+            self.client
+                .get(format!("{}/sites/{}", &self.config.wpengine_api,  id))
+        )
     }
 
     /// Try to add a site.
     pub fn add_site(&self, body: &Site) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .post(&format!("{}/sites", &self.config.wpengine_api))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .json(body)
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+        self.send(
+            self.client
+                .post(format!("{}/sites", &self.config.wpengine_api))
+                .json(body)
+        )
     }
 
     pub fn update_site(&self, id: &str, body: &SitePatch)
         -> Result<serde_json::Value, anyhow::Error> {
 
-        let res = self
-            .client
-            .patch(&format!("{}/sites/{}", &self.config.wpengine_api, id))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .json(body)
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+        self.send(
+            self.client
+                .patch(format!("{}/sites/{}", &self.config.wpengine_api, id))
+                .json(body)
+        )
     }
 
     /// Try to delete a specific install.
     pub fn delete_site(&self, id: &str ) -> Result<serde_json::Value, anyhow::Error>{
-        let res = self
-            .client
-            .delete(&format!("{}/sites/{}", &self.config.wpengine_api, id))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+        self.send(
+            self.client
+                .delete(format!("{}/sites/{}", &self.config.wpengine_api, id))
+        )
     }
 
-    /// Get all installs from wpengine. Pass an optional page number to show more results.
-    pub fn get_installs(&self, page: Option<u8>) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .get(&format!("{}/installs?offset={}", &self.config.wpengine_api, page.unwrap_or(0) * 100))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+    /// Get all installs from wpengine. Pass an optional page number to show more results
+    /// and an optional account ID to only list that account's installs.
+    pub fn get_installs(&self, page: Option<u32>, account_id: Option<&str>)
+        -> Result<serde_json::Value, anyhow::Error> {
+        self.send(
+            self.client
+                .get(format!("{}/installs?offset={}", &self.config.wpengine_api, u64::from(page.unwrap_or(0)) * 100))
+                .query(&[("account_id", account_id)])
+        )
     }
 
     /// Get a single install by its ID from the wpengine API
     pub fn get_install_by_id(&self, id: &str) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .get(&format!("{}/installs/{}", &self.config.wpengine_api,  id))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+        self.send(
+            self.client
+                .get(format!("{}/installs/{}", &self.config.wpengine_api,  id))
+        )
     }
 
     /// Try to add an install instance.
     pub fn add_install(&self, body: &Install) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .post(&format!("{}/installs", &self.config.wpengine_api))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .json(body)
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+        self.send(
+            self.client
+                .post(format!("{}/installs", &self.config.wpengine_api))
+                .json(body)
+        )
     }
 
     pub fn update_install(&self, install_id: &str, body: &InstallPatch)
         -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .patch(&format!("{}/installs/{}", &self.config.wpengine_api, install_id))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .json(body)
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+        self.send(
+            self.client
+                .patch(format!("{}/installs/{}", &self.config.wpengine_api, install_id))
+                .json(body)
+        )
     }
 
     pub fn purge_cache(&self, id: &str, body: String) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .post(&format!("{}/installs/{}/purge_cache", &self.config.wpengine_api, id))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .json(&Cache {
-                r#type: body
-            })
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+        self.send(
+            self.client
+                .post(format!("{}/installs/{}/purge_cache", &self.config.wpengine_api, id))
+                .json(&Cache {
+                    r#type: body
+                })
+        )
     }
 
+    /// Request a backup of an install and record it in the local ledger.
     pub fn backup(&self, id: &str, backup: &Backup) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .post(&format!("{}/installs/{}/backups", &self.config.wpengine_api, id))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .json(backup)
-            .send()?
-            .json::<serde_json::Value>()?;
+        let res = self.send(
+            self.client
+                .post(format!("{}/installs/{}/backups", &self.config.wpengine_api, id))
+                .json(backup)
+        )?;
+
+        if let Some(backup_id) = res["id"].as_str() {
+            let recorded = BackupLedger::load().and_then(|mut ledger| {
+                ledger.backups.push(BackupEntry {
+                    id: backup_id.to_string(),
+                    install_id: id.to_string(),
+                    description: backup.description.clone(),
+                    created_at: Utc::now(),
+                    status: res["status"].as_str().unwrap_or("requested").to_string(),
+                    error: None
+                });
+                ledger.save()
+            });
+            // The backup was requested either way, only the local record of it is missing.
+            if let Err(err) = recorded {
+                eprintln!("The backup {} was requested but couldn't be recorded: {:#}", backup_id, err);
+            }
+        }
 
         Ok(res)
     }
 
     pub fn get_backup(&self, install_id: &str, backup_id: &str) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .get(&format!("{}/installs/{}/backups/{}", &self.config.wpengine_api, install_id, backup_id))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+        self.send(
+            self.client
+                .get(format!("{}/installs/{}/backups/{}", &self.config.wpengine_api, install_id, backup_id))
+        )
     }
 
     /// Try to delete a specific install.
     pub fn delete_install(&self, id: &str ) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .delete(&format!("{}/installs/{}", &self.config.wpengine_api, id))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+        self.send(
+            self.client
+                .delete(format!("{}/installs/{}", &self.config.wpengine_api, id))
+        )
     }
 
     /// List all accounts, optional page offset.
-    pub fn get_accounts(&self, page: Option<u8>) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .get(&format!("{}/accounts?offset={}", &self.config.wpengine_api, page.unwrap_or(0) * 100))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+    pub fn get_accounts(&self, page: Option<u32>) -> Result<serde_json::Value, anyhow::Error> {
+        self.send(
+            self.client
+                .get(format!("{}/accounts?offset={}", &self.config.wpengine_api, u64::from(page.unwrap_or(0)) * 100))
+        )
     }
 
     /// Get the currently authenticated user's account details.
     pub fn get_user(&self) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .get(&format!("{}/user", &self.config.wpengine_api))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+        self.send(
+            self.client
+                .get(format!("{}/user", &self.config.wpengine_api))
+        )
     }
     /// List account by ID.
     pub fn get_account_by_id(&self, id: &str) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .get(&format!("{}/accounts/{}", &self.config.wpengine_api,  id))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+        self.send(
+            self.client
+                .get(format!("{}/accounts/{}", &self.config.wpengine_api,  id))
+        )
     }
 
     /// Add a user to a specific account.
     pub fn add_user(&self, id: &str, user: &AccountUser) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .post(&format!("{}/accounts/{}/account_users", &self.config.wpengine_api, id))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .json(user)
-            .send()?
-            .json::<serde_json::Value>()?;
+        self.send(
+            self.client
+                .post(format!("{}/accounts/{}/account_users", &self.config.wpengine_api, id))
+                .json(user)
+        )
+    }
 
-        Ok(res)
+    /// List the users of an account, optional page offset.
+    pub fn get_account_users(&self, account_id: &str, page: Option<u32>) -> Result<serde_json::Value, anyhow::Error> {
+        self.send(
+            self.client
+                .get(format!(
+                    "{}/accounts/{}/account_users?offset={}",
+                    &self.config.wpengine_api,
+                    account_id,
+                    u64::from(page.unwrap_or(0)) * 100
+                ))
+        )
     }
 
     pub fn get_user_by_id(&self, account_id: &str, user_id: &str) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .get(&format!("{}/accounts/{}/account_users/{}", &self.config.wpengine_api, account_id, user_id))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+        self.send(
+            self.client
+                .get(format!("{}/accounts/{}/account_users/{}", &self.config.wpengine_api, account_id, user_id))
+        )
     }
 
     pub fn update_user(&self, account_id: &str, user_id: &str, body: &AccountUserPatch)
         -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .patch(&format!("{}/accounts/{}/account_users/{}", &self.config.wpengine_api, account_id, user_id))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .json(body)
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+        self.send(
+            self.client
+                .patch(format!("{}/accounts/{}/account_users/{}", &self.config.wpengine_api, account_id, user_id))
+                .json(body)
+        )
     }
 
     /// Try to delete a user from an account.
     pub fn delete_user(&self, account_id: &str, user_id: &str ) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .delete(&format!("{}/accounts/{}/account_users/{}", &self.config.wpengine_api, account_id, user_id))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+        self.send(
+            self.client
+                .delete(format!("{}/accounts/{}/account_users/{}", &self.config.wpengine_api, account_id, user_id))
+        )
     }
 
     /// Get a list of ssh keys for authorized user.
-    pub fn get_ssh_keys(&self, page: Option<i32>) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .get(&format!("{}/ssh_keys?offset={}", &self.config.wpengine_api, page.unwrap_or(0) * 100))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+    pub fn get_ssh_keys(&self, page: Option<u32>) -> Result<serde_json::Value, anyhow::Error> {
+        self.send(
+            self.client
+                .get(format!("{}/ssh_keys?offset={}", &self.config.wpengine_api, u64::from(page.unwrap_or(0)) * 100))
+        )
     }
 
     /// Add an ssh key to the authorized users account.
     pub fn add_ssh_key(&self, ssh_key: &SSHKey) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .post(&format!("{}/ssh_keys", &self.config.wpengine_api))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .json(ssh_key)
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+        self.send(
+            self.client
+                .post(format!("{}/ssh_keys", &self.config.wpengine_api))
+                .json(ssh_key)
+        )
     }
 
     /// Delete an ssh key from the authorized users account.
     pub fn delete_ssh_key(&self, id: &str) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .delete(&format!("{}/ssh_keys/{}", &self.config.wpengine_api, id))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+        self.send(
+            self.client
+                .delete(format!("{}/ssh_keys/{}", &self.config.wpengine_api, id))
+        )
     }
 
     /// Get domains from an install
-    pub fn get_domains(&self, id: &String,  page: Option<u8>) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .get(&format!("{}/installs/{}/domains?offset={}", &self.config.wpengine_api, id, page.unwrap_or(0) * 100))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+    pub fn get_domains(&self, id: &String,  page: Option<u32>) -> Result<serde_json::Value, anyhow::Error> {
+        self.send(
+            self.client
+                .get(format!("{}/installs/{}/domains?offset={}", &self.config.wpengine_api, id, u64::from(page.unwrap_or(0)) * 100))
+        )
     }
 
     pub fn get_domain_by_id(&self, install_id: &str, domain_id: &str) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .get(&format!("{}/installs/{}/domains/{}", &self.config.wpengine_api, install_id, domain_id))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+        self.send(
+            self.client
+                .get(format!("{}/installs/{}/domains/{}", &self.config.wpengine_api, install_id, domain_id))
+        )
     }
 
     pub fn add_domain(&self, id: &str, domain: &Domain) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .post(&format!("{}/installs/{}/domains", &self.config.wpengine_api, id))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .json(domain)
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+        self.send(
+            self.client
+                .post(format!("{}/installs/{}/domains", &self.config.wpengine_api, id))
+                .json(domain)
+        )
     }
 
     pub fn update_domain(&self, install_id: &str, domain_id: &str, data: &DomainPatch)
         -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .patch(&format!("{}/installs/{}/domains/{}", &self.config.wpengine_api, install_id, domain_id))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .json(data)
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+        self.send(
+            self.client
+                .patch(format!("{}/installs/{}/domains/{}", &self.config.wpengine_api, install_id, domain_id))
+                .json(data)
+        )
     }
 
     pub fn delete_domain(&self, install_id: &str, domain_id: &str) -> Result<serde_json::Value, anyhow::Error> {
-        let res = self
-            .client
-            .delete(&format!("{}/installs/{}/domains/{}", &self.config.wpengine_api, install_id, domain_id))
-            .basic_auth(
-                &self.config.wpengine_user_id,
-                Some(&self.config.wpengine_password)
-            )
-            .send()?
-            .json::<serde_json::Value>()?;
-
-        Ok(res)
+        self.send(
+            self.client
+                .delete(format!("{}/installs/{}/domains/{}", &self.config.wpengine_api, install_id, domain_id))
+        )
     }
 }
 
-#[macro_export]
-macro_rules! get_selections {
-    ($item:ident, $prompt:literal, $id:literal) => {
-        {
-            let selection = Select::with_theme(&ColorfulTheme::default())
-                .with_prompt($prompt)
-                .items(&$item
-                       .iter()
-                       .map(|i| &i[$id])
-                       .collect::<Vec<&serde_json::Value>>()
-                      )
-                .interact()?;
-            selection
+const LOAD_MORE: &str = "» Load more…";
+const BACK: &str = "« Back";
+
+/// Fetches one page of a listing for a [`Picker`].
+type PageFetcher<'a> = Box<dyn FnMut(u32) -> Result<serde_json::Value> + 'a>;
+
+/// Decides which fetched items a [`Picker`] offers.
+type ItemFilter<'a> = Box<dyn Fn(&serde_json::Value) -> bool + 'a>;
+
+/// Fuzzy-search picker over API results.
+/**
+  - Paged pickers fetch further pages on demand through a "Load more" entry.
+  - Every picker ends with a "Back" entry, which like Esc returns `None`.
+  */
+pub struct Picker<'a> {
+    prompt: String,
+    key: String,
+    items: Vec<serde_json::Value>,
+    fetch: Option<PageFetcher<'a>>,
+    filter: Option<ItemFilter<'a>>,
+    page: u32,
+    has_more: bool
+}
+
+impl<'a> Picker<'a> {
+    /// Pick from a list that is already loaded.
+    pub fn new(prompt: &str, key: &str, items: Vec<serde_json::Value>) -> Self {
+        Self {
+            prompt: prompt.to_string(),
+            key: key.to_string(),
+            items,
+            fetch: None,
+            filter: None,
+            page: 0,
+            has_more: false
         }
-    };
+    }
+
+    /// Pick from a paginated listing, e.g. `|page| api.get_sites(Some(page), None)`.
+    pub fn paged<F>(prompt: &str, key: &str, fetch: F) -> Self
+    where
+        F: FnMut(u32) -> Result<serde_json::Value> + 'a
+    {
+        Self {
+            fetch: Some(Box::new(fetch)),
+            has_more: true,
+            ..Self::new(prompt, key, Vec::new())
+        }
+    }
+
+    /// Only offer the items of each fetched page that pass the filter.
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&serde_json::Value) -> bool + 'a
+    {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    fn load_page(&mut self) -> Result<()> {
+        if let Some(fetch) = self.fetch.as_mut() {
+            let res = fetch(self.page)?;
+            let page = res["results"].as_array().cloned().unwrap_or_default();
+
+            self.has_more = !page.is_empty() && !res["next"].is_null();
+            self.page += 1;
+            self.items.extend(
+                page.into_iter().filter(|i| self.filter.as_ref().is_none_or(|f| f(i)))
+            );
+        }
+        Ok(())
+    }
+
+    /// Show the picker. Returns the chosen item, or `None` if the user went back.
+    pub fn interact(&mut self) -> Result<Option<serde_json::Value>> {
+        // Skip past pages the filter emptied out so there is something to show.
+        while self.items.is_empty() && self.has_more {
+            self.load_page()?;
+        }
+
+        let mut default = 0;
+        loop {
+            let mut labels: Vec<String> = self.items
+                .iter()
+                .map(|i| match &i[self.key.as_str()] {
+                    serde_json::Value::String(s) => s.to_string(),
+                    v => v.to_string()
+                })
+                .collect();
+            if self.has_more {
+                labels.push(LOAD_MORE.to_string());
+            }
+            labels.push(BACK.to_string());
+
+            let selection = FuzzySelect::with_theme(&ColorfulTheme::default())
+                .with_prompt(&self.prompt)
+                .items(&labels)
+                .default(default)
+                .interact_opt()?;
+
+            match selection {
+                Some(i) if i < self.items.len() => return Ok(Some(self.items[i].clone())),
+                Some(i) if self.has_more && i == self.items.len() => {
+                    // Land on the first new entry once the next page is in.
+                    default = self.items.len();
+                    self.load_page()?;
+                },
+                _ => return Ok(None)
+            }
+        }
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(updated_at: Option<DateTime<Utc>>) -> ResourceIndex {
+        ResourceIndex {
+            updated_at,
+            installs: vec![IndexEntry { id: "0000003e-0000-4000-8000-00000000003e".to_string(), name: "acmeprod".to_string() }],
+            ..Default::default()
+        }
+    }
+
+    fn backup(id: &str, install_id: &str, hours_ago: i64, status: &str) -> BackupEntry {
+        BackupEntry {
+            id: id.to_string(),
+            install_id: install_id.to_string(),
+            description: String::new(),
+            created_at: Utc::now() - chrono::Duration::hours(hours_ago),
+            status: status.to_string(),
+            error: None
+        }
+    }
+
+    #[test]
+    fn lists_backups_newest_first_by_install() {
+        let ledger = BackupLedger {
+            backups: vec![backup("b1", "i1", 3, "completed"), backup("b2", "i2", 2, "requested"), backup("b3", "i1", 1, "requested")]
+        };
+        let ids = |entries: Vec<&BackupEntry>| entries.iter().map(|b| b.id.clone()).collect::<Vec<_>>();
+
+        assert_eq!(ids(ledger.list(None)), vec!["b3", "b2", "b1"]);
+        assert_eq!(ids(ledger.list(Some("i1"))), vec!["b3", "b1"]);
+        assert!(ledger.list(Some("i3")).is_empty());
+    }
+
+    #[test]
+    fn only_unfinished_backups_are_pending() {
+        for status in ["requested", "in_progress", "pending", ""] {
+            assert!(backup("b", "i", 0, status).is_pending(), "{}", status);
+        }
+        for status in ["completed", "failed", "aborted", "not_found"] {
+            assert!(!backup("b", "i", 0, status).is_pending(), "{}", status);
+        }
+    }
+
+    fn redacted(mut value: serde_json::Value) -> serde_json::Value {
+        redact(&mut value);
+        value
+    }
+
+    #[test]
+    fn redacts_secrets_at_any_depth() {
+        let body = redacted(serde_json::json!({ "user": { "name": "ann", "credentials": { "password": "hunter2" } } }));
+        assert_eq!(body, serde_json::json!({ "user": { "name": "ann", "credentials": { "password": "[REDACTED]" } } }));
+    }
+
+    #[test]
+    fn redacts_every_body_of_an_array() {
+        let body = redacted(serde_json::json!([{ "secret": "a", "id": 1 }, { "secret": "b", "id": 2 }]));
+        assert_eq!(body, serde_json::json!([{ "secret": "[REDACTED]", "id": 1 }, { "secret": "[REDACTED]", "id": 2 }]));
+    }
+
+    #[test]
+    fn matches_secret_keys_ignoring_case_and_within_names() {
+        let body = redacted(serde_json::json!({
+            "Password": "a",
+            "api_token": "b",
+            "SSH_PRIVATE_KEY": { "pem": "c" },
+            "description": "token rotation"
+        }));
+        assert_eq!(body, serde_json::json!({
+            "Password": "[REDACTED]",
+            "api_token": "[REDACTED]",
+            "SSH_PRIVATE_KEY": "[REDACTED]",
+            "description": "token rotation"
+        }));
+    }
+
+    #[test]
+    fn index_goes_stale_with_age() {
+        assert!(index(None).is_stale());
+        assert!(!index(Some(Utc::now() - chrono::Duration::hours(1))).is_stale());
+        assert!(index(Some(Utc::now() - chrono::Duration::hours(INDEX_MAX_AGE + 1))).is_stale());
+    }
+
+    #[test]
+    fn finds_listed_ids_in_request_paths() {
+        let index = index(Some(Utc::now()));
+
+        assert!(index.lists_id_in("/v1/installs/0000003e-0000-4000-8000-00000000003e"));
+        assert!(index.lists_id_in("/v1/installs/0000003e-0000-4000-8000-00000000003e/domains"));
+        assert!(!index.lists_id_in("/v1/installs/0000003e-0000-4000-8000-00000000003f"));
+        assert!(!index.lists_id_in("/v1/installs"));
+    }
+}
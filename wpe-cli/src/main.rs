@@ -1,113 +1,71 @@
-use clap::{arg, Command};
+use clap::Parser;
 use anyhow::Result;
+use wpe::{CliError, ErrorKind};
+use cli::{Cli, Commands, AuthCommand};
+mod cli;
 mod sites;
 mod installs;
 mod accounts;
+mod users;
+mod domains;
+mod ssh_keys;
+mod backups;
+mod guard;
+mod diff;
+mod edit;
+mod input;
+mod import;
+mod manifest;
+mod export;
+mod cache;
+mod output;
+mod filter;
+mod bulk;
+mod session;
+mod shell;
+mod tui;
+mod tree;
+mod complete;
+mod docs;
 
-/// Setup the CLI and build the commands.
-fn cli() -> Command {
-    Command::new("wpe")
-        .about("WPEngine CLI")
-        .arg(arg!(-H --headless "Enables headless mode").required(false))
-        .subcommand_required(true)
-        .arg_required_else_help(true)
-        .allow_external_subcommands(true)
-        .subcommand(
-            Command::new("sites")
-                .about("Display list of sites as selection.")
-                .arg(arg!(<PAGE> "The page number").required(false))
-                .after_help("Selecting one will fetch the site and display more options.")
-                .subcommand(
-                    Command::new("list")
-                        .about("List sites.")
-                        .arg(arg!(<ID> "Account ID").required(false))
-                )
-                .subcommand(
-                    Command::new("add")
-                        .about("Add a site using headless mode")
-                        .arg(arg!(<NAME> "Site name").required(true))
-                        .arg(arg!(<ID> "Account ID").required(true))
-                )
-                .subcommand(
-                    Command::new("update")
-                        .about("Update the name of a site.")
-                        .arg(arg!(<NAME> "Site name").required(true))
-                        .arg(arg!(<ID> "Site ID").required(true))
-                )
-                .subcommand(
-                    Command::new("delete")
-                        .about("Delete a site.")
-                        .arg(arg!(<ID> "Site ID").required(true))
-                )
-        )
-        .subcommand(
-            Command::new("installs")
-                .about("Display list of installs as selection.")
-                .arg(arg!(<PAGE> "The page number").required(false))
-                .after_help("Selecting one will fetch the site and display more options.")
-                .subcommand(
-                    Command::new("list")
-                        .about("List sites.")
-                        .arg(arg!(<ID> "Account ID").required(false))
-                )
-                .subcommand(
-                    Command::new("add")
-                        .about("Add a site using headless mode")
-                        .arg(arg!(<NAME> "Site name").required(true))
-                        .arg(arg!(<ACCOUNT> "Account ID").required(true))
-                        .arg(arg!(<SITE> "Site ID").required(true))
-                        .arg(arg!(<ENV> "Environment").required(true))
-                )
-                .subcommand(
-                    Command::new("update")
-                        .about("Update the name of a site.")
-                        .arg(arg!(<ID> "Install ID").required(true))
-                        .arg(arg!(<SITE> "Site ID").required(false))
-                        .arg(arg!(<ENV> "Environment").required(false))
-                )
-                .subcommand(
-                    Command::new("delete")
-                        .about("Delete an install.")
-                        .arg(arg!(<ID> "Install ID").required(true))
-                )
-        )
-        .subcommand(
-            Command::new("accounts")
-                .about("Fetch all sites from your wpengine account")
-                .arg(arg!(<PAGE> "The page number").required(false))
-        )
-        .subcommand(
-            Command::new("account")
-                .about("Fetch an account by its ID")
-                .arg(arg!(<ID> "The account ID"))
-                .arg_required_else_help(true),
-        )
-        .subcommand(
-            Command::new("auth")
-                .about("Authenticate with WP Engine API")
-                .subcommand(
-                    Command::new("login")
-                    .about("Login to WP Engine API")
-                )
-                .subcommand(
-                    Command::new("reset")
-                        .about("Reset authentication")
-                )
-                .subcommand_required(true)
-        )
-        .subcommand(
-            Command::new("status")
-                .about("Get API status")
-        )
-        .subcommand(
-            Command::new("swagger")
-                .about("Get API swagger")
-        )
+fn main() {
+    let cli = Cli::try_parse().unwrap_or_else(|e| usage_error(e));
+
+    if let Err(err) = start(&cli) {
+        std::process::exit(output::print_error(&err, cli.global.output));
+    }
+}
+
+/// Report a command line that couldn't be parsed and exit with the usage code.
+/// Help and version requests print as usual.
+fn usage_error(e: clap::Error) -> ! {
+    let args: Vec<String> = std::env::args().collect();
+    let json = args.windows(2).any(|w| w[0] == "--output" && w[1] == "json")
+        || args.iter().any(|a| a == "--output=json");
+
+    if !e.use_stderr() || !json {
+        e.exit();
+    }
+
+    let err = CliError::new(ErrorKind::Usage, e.render().to_string().trim().to_string()).into();
+    std::process::exit(output::print_error(&err, Some(output::Format::Json)))
 }
 
-fn main() -> Result<()> {
+/// Authenticate and run the parsed command.
+fn start(cli: &Cli) -> Result<()> {
+    // Completions and docs are generated offline and must never prompt for credentials.
+    match &cli.command {
+        Commands::Completions { shell } => return complete::init(*shell),
+        Commands::Complete { words } => return complete::hook(words),
+        Commands::Docs { command } => return docs::init(command),
+        _ => {}
+    }
+
     // Check if authentication exists, else handle authentication.
-    wpe::init()?;
+    // Logging in is exempt, it prompts for the credentials itself.
+    if !matches!(cli.command, Commands::Auth { .. }) {
+        wpe::init(cli.global.headless())?;
+    }
 
     // Handle missing cursor when pressing ctrl-c to quit.
     ctrlc::set_handler(move || {
@@ -116,53 +74,107 @@ fn main() -> Result<()> {
     })?;
 
     // Initiate CLI commands.
-    let matches = cli().get_matches();
-    let command = wpe::API::new();
-    let headless = matches.get_one::<bool>("headless");
+    let command = wpe::API::new().dry_run(cli.global.dry_run);
+
+    run(cli, &command)
+}
+
+/// Handle a parsed command line. The shell runs each line it reads through here.
+fn run(cli: &Cli, command: &wpe::API) -> Result<()> {
+    let global = &cli.global;
+    let headless = global.headless();
+    let out = output::Output::new(global.output, &global.fields, global.query.as_deref())?;
+    let account = global
+        .account
+        .as_ref()
+        .map(|a| command.resolve(wpe::Resource::Account, a))
+        .transpose()?;
+    let account = account.as_deref();
 
     // Handle logic for each command.
-    match matches.subcommand() {
-        Some(("sites", sub_n)) => {
+    match &cli.command {
+        Commands::Sites(args) => {
             // Initialize [sites] command logic.
-            sites::init(sub_n, command, headless)?;
+            sites::init(args, command, headless, &out, account)?;
+        },
+        Commands::Installs(args) => {
+            installs::init(args, command, headless, &out, account)?;
         },
-        Some(("installs", sub_n)) => {
-            installs::init(sub_n, command, headless)?;
-        }
-        Some(("accounts", sub_n)) => {
+        Commands::Accounts(args) => {
             // Initialize [accounts] command logic.
-            accounts::init(sub_n, command, headless)?;
+            accounts::init(args, command, headless, &out)?;
+        },
+        Commands::Users(args) => {
+            users::init(args, command, headless, &out, account)?;
         },
-        Some(("account", sub_m)) => {
-            // This will eventually be moved to the [accounts] command.
-            let id = sub_m.get_one::<String>("ID").unwrap();
-            let res = command.get_account_by_id(id)?;
-            println!("{}", serde_json::to_string_pretty(&res)?);
+        Commands::Domains(args) => {
+            domains::init(args, command, headless, &out)?;
         },
-        Some(("auth", sub_m)) => {
-            match sub_m.subcommand() {
-                Some(("login", _)) => {
+        Commands::SshKeys(args) => {
+            ssh_keys::init(args, command, headless, &out)?;
+        },
+        Commands::Backups(args) => {
+            // Initialize [backups] command logic.
+            backups::init(args, command, &out)?;
+        },
+        Commands::Cache(args) => {
+            cache::init(args, command, &out)?;
+        },
+        Commands::Import { file, results, concurrency, yes } => {
+            import::init(file, results.as_deref(), *concurrency, *yes, command, headless, &out)?;
+        },
+        Commands::Plan { file, prune } => {
+            manifest::plan(file, *prune, command, &out)?;
+        },
+        Commands::Apply { file, prune, confirm } => {
+            manifest::apply(file, *prune, confirm, command, headless)?;
+        },
+        Commands::Export { out: file } => {
+            export::init(file.as_deref(), command, &out, account)?;
+        },
+        Commands::Edit { command: edit } => {
+            edit::init(edit, command, headless, &out, account)?;
+        },
+        Commands::Auth { command: auth } => {
+            match auth {
+                AuthCommand::Login => {
+                    if headless {
+                        return Err(CliError::new(
+                            ErrorKind::Usage,
+                            "`wpe auth login` prompts for credentials, run it in a terminal or pass --interactive."
+                        ).into());
+                    }
                     wpe::set_auth()?;
                 },
-                Some(("reset", _)) => {
-                    wpe::reset()?;
-                },
-                _ => println!("Error with auth command.")
+                AuthCommand::Reset => {
+                    wpe::reset(command.is_dry_run())?;
+                }
             }
         },
-        Some(("status", _)) => {
+        Commands::Status => {
             // This endpoint will report the system status
             // and any outages that might be occurring.
             let status = command.status()?;
-            println!("{}", serde_json::to_string_pretty(&status)?)
+            out.print(&status, output::Kind::Other)?;
         },
-        Some(("swagger", _)) => {
-            // This endpoint will report the system status
-            // and any outages that might be occurring.
+        Commands::Tree { depth } => {
+            // Initialize [tree] command logic.
+            tree::init(*depth, command, &out, account)?;
+        },
+        Commands::Shell => {
+            // Initialize [shell] command logic.
+            shell::init(command, cli.global.output)?;
+        },
+        Commands::Tui { interval } => {
+            // Initialize [tui] command logic.
+            tui::init(*interval, command, account)?;
+        },
+        Commands::Swagger => {
             let swagger = command.swagger()?;
-            println!("{}", serde_json::to_string_pretty(&swagger)?)
-        }
-        _ => println!("Invalid command. Please use <help> to a see full list of commands.")
+            out.print(&swagger, output::Kind::Other)?;
+        },
+        // Handled before authentication in main.
+        Commands::Completions { .. } | Commands::Complete { .. } | Commands::Docs { .. } => {}
     }
     Ok(())
 }
@@ -0,0 +1,1055 @@
+use std::collections::{HashMap, HashSet};
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use console::style;
+use dialoguer::Confirm;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use wpe::*;
+use crate::output::{self, Output, Kind, Format};
+use crate::cli::{Confirmation, Environment};
+use crate::{diff, input};
+
+/// The layout a manifest describes. A collection that is left out isn't managed,
+/// an empty list means there should be none.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub accounts: Vec<AccountSpec>
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct AccountSpec {
+    /// Account name or ID, accounts themselves can't be created.
+    pub name: String,
+    pub sites: Option<Vec<SiteSpec>>,
+    pub users: Option<Vec<UserSpec>>
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SiteSpec {
+    pub name: String,
+    pub installs: Option<Vec<InstallSpec>>
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct InstallSpec {
+    pub name: String,
+    pub environment: String,
+    pub domains: Option<Vec<DomainSpec>>
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct DomainSpec {
+    pub name: String,
+    /// Left out, a new domain isn't primary and an existing one keeps its setting.
+    pub primary: Option<bool>,
+    /// Name of another domain of the install.
+    pub redirect_to: Option<String>
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct UserSpec {
+    pub email: String,
+    /// Names and roles are needed to invite a user, existing users keep theirs when left out.
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub roles: Option<String>,
+    /// Names of the installs a partial user can access.
+    pub installs: Option<Vec<String>>
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Op {
+    Create,
+    Update,
+    Delete
+}
+
+/// The ID of a resource, or the address of one the apply creates first.
+#[derive(Clone, Debug)]
+enum Ref {
+    Id(String),
+    New(String)
+}
+
+impl Ref {
+    fn id(&self, ids: &HashMap<String, String>, api: &API) -> Result<String> {
+        match self {
+            Ref::Id(id) => Ok(id.clone()),
+            Ref::New(address) => ids
+                .get(address)
+                .cloned()
+                // A dry run sends nothing, so there's no ID to show.
+                .or_else(|| api.is_dry_run().then(|| format!("<{}>", address)))
+                .ok_or_else(|| anyhow!("{} was not created.", address))
+        }
+    }
+}
+
+/// A request of an apply.
+#[derive(Debug)]
+enum Step {
+    CreateSite { address: String, site: Site },
+    CreateInstall { address: String, name: String, account_id: String, site: Ref, environment: String },
+    UpdateInstall { id: String, site: Option<Ref>, environment: Option<String> },
+    CreateDomain { address: String, install: Ref, domain: Domain },
+    UpdateDomain { install: Ref, domain: Ref, primary: Option<bool>, redirect_to: Option<Ref> },
+    CreateUser { account_id: String, user: User, installs: Vec<Ref> },
+    UpdateUser { account_id: String, id: String, roles: Option<String>, installs: Option<Vec<Ref>> },
+    DeleteUser { account_id: String, id: String },
+    DeleteDomain { install_id: String, id: String },
+    DeleteInstall { id: String },
+    DeleteSite { id: String }
+}
+
+impl Step {
+    /// When the step runs: what it needs is created first and deleted last.
+    fn stage(&self) -> u8 {
+        match self {
+            Step::CreateSite { .. } => 0,
+            Step::CreateInstall { .. } | Step::UpdateInstall { .. } => 1,
+            Step::CreateDomain { .. } => 2,
+            // The new primary is set before the old one is unset, so the install always has one.
+            Step::UpdateDomain { primary: Some(true), .. } => 3,
+            Step::UpdateDomain { .. } => 4,
+            Step::CreateUser { .. } | Step::UpdateUser { .. } => 5,
+            Step::DeleteUser { .. } => 6,
+            Step::DeleteDomain { .. } => 7,
+            Step::DeleteInstall { .. } => 8,
+            Step::DeleteSite { .. } => 9
+        }
+    }
+
+    /// Send the request, recording the IDs of what it creates.
+    fn run(&self, api: &API, ids: &mut HashMap<String, String>) -> Result<()> {
+        let created = match self {
+            Step::CreateSite { address, site } => Some((address, api.add_site(site)?)),
+            Step::CreateInstall { address, name, account_id, site, environment } => {
+                let install = Install {
+                    name: name.clone(),
+                    account_id: account_id.clone(),
+                    site_id: site.id(ids, api)?,
+                    environment: environment.clone()
+                };
+                Some((address, api.add_install(&install)?))
+            },
+            Step::UpdateInstall { id, site, environment } => {
+                let data = InstallPatch {
+                    site_id: site.as_ref().map(|s| s.id(ids, api)).transpose()?,
+                    environment: environment.clone()
+                };
+                api.update_install(id, &data)?;
+                None
+            },
+            Step::CreateDomain { address, install, domain } => Some((address, api.add_domain(&install.id(ids, api)?, domain)?)),
+            Step::UpdateDomain { install, domain, primary, redirect_to } => {
+                let data = DomainPatch {
+                    primary: *primary,
+                    redirect_to: redirect_to.as_ref().map(|d| d.id(ids, api)).transpose()?
+                };
+                api.update_domain(&install.id(ids, api)?, &domain.id(ids, api)?, &data)?;
+                None
+            },
+            Step::CreateUser { account_id, user, installs } => {
+                let user = User {
+                    install_ids: installs.iter().map(|i| i.id(ids, api)).collect::<Result<_>>()?,
+                    ..user.clone()
+                };
+                api.add_user(account_id, &AccountUser { user })?;
+                None
+            },
+            Step::UpdateUser { account_id, id, roles, installs } => {
+                let data = AccountUserPatch {
+                    user: UserPatch {
+                        roles: roles.clone(),
+                        install_ids: installs
+                            .as_ref()
+                            .map(|i| i.iter().map(|i| i.id(ids, api)).collect::<Result<_>>())
+                            .transpose()?
+                    }
+                };
+                api.update_user(account_id, id, &data)?;
+                None
+            },
+            Step::DeleteUser { account_id, id } => {
+                api.delete_user(account_id, id)?;
+                None
+            },
+            Step::DeleteDomain { install_id, id } => {
+                api.delete_domain(install_id, id)?;
+                None
+            },
+            Step::DeleteInstall { id } => {
+                api.delete_install(id)?;
+                None
+            },
+            Step::DeleteSite { id } => {
+                api.delete_site(id)?;
+                None
+            }
+        };
+
+        if let Some((address, id)) = created.and_then(|(a, v)| v["id"].as_str().map(|id| (a, id.to_string()))) {
+            ids.insert(address.clone(), id);
+        }
+
+        Ok(())
+    }
+}
+
+/// A resource the apply creates, updates or deletes.
+pub struct Change {
+    pub op: Op,
+    pub kind: &'static str,
+    pub address: String,
+    pub fields: Vec<diff::Change>,
+    /// Anything that goes with it, e.g. the installs of a deleted site.
+    pub note: Option<String>,
+    /// Deleting it takes a production install with it.
+    pub production: bool,
+    steps: Vec<Step>
+}
+
+impl Change {
+    fn new(op: Op, kind: &'static str, address: String, steps: Vec<Step>) -> Self {
+        Self { op, kind, address, fields: Vec::new(), note: None, production: false, steps }
+    }
+
+    /// Add a field, as a change from `before` for an update or a new value for a create.
+    fn field(&mut self, field: &str, before: Value, after: Value) {
+        self.fields.push(diff::Change { field: field.to_string(), before, after });
+    }
+
+    fn to_json(&self) -> Value {
+        let fields: serde_json::Map<String, Value> = self.fields
+            .iter()
+            .map(|f| (f.field.clone(), json!({ "before": f.before, "after": f.after })))
+            .collect();
+
+        json!({
+            "action": self.op,
+            "kind": self.kind,
+            "address": self.address,
+            "fields": fields,
+            "note": self.note
+        })
+    }
+}
+
+/// The changes that bring the live state in line with a manifest.
+pub struct Plan {
+    pub changes: Vec<Change>,
+    /// Resources missing from the manifest, only deleted with `--prune`.
+    pub unmanaged: Vec<Change>
+}
+
+impl Plan {
+    fn count(&self, op: Op) -> usize {
+        self.changes.iter().filter(|c| c.op == op).count()
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "{} to add, {} to change, {} to delete.",
+            self.count(Op::Create),
+            self.count(Op::Update),
+            self.count(Op::Delete)
+        )
+    }
+
+    /// The plan as terraform shows it, with a symbol and colour per action.
+    fn render(&self) -> String {
+        let mut text = String::new();
+
+        for change in &self.changes {
+            let (symbol, header) = match change.op {
+                Op::Create => (style("+").green().bold(), style(format!("{} {}", change.kind, change.address)).green()),
+                Op::Update => (style("~").yellow().bold(), style(format!("{} {}", change.kind, change.address)).yellow()),
+                Op::Delete => (style("-").red().bold(), style(format!("{} {}", change.kind, change.address)).red())
+            };
+            text.push_str(&format!("  {} {}", symbol, header));
+            if let Some(note) = &change.note {
+                text.push_str(&format!(" {}", style(format!("({})", note)).dim()));
+            }
+            text.push('\n');
+
+            for field in &change.fields {
+                let line = match change.op {
+                    Op::Update => format!(
+                        "{} → {}",
+                        style(diff::show(&field.before)).red(),
+                        style(diff::show(&field.after)).green()
+                    ),
+                    _ => diff::show(&field.after)
+                };
+                text.push_str(&format!("      {}: {}\n", field.field, line));
+            }
+        }
+
+        if self.changes.is_empty() {
+            text.push_str("No changes, the live state matches the manifest.\n");
+        } else {
+            text.push_str(&format!("\n{} {}\n", style("Plan:").bold(), self.summary()));
+        }
+
+        if !self.unmanaged.is_empty() {
+            text.push_str("\nNot in the manifest, pass --prune to delete:\n");
+            for change in &self.unmanaged {
+                let note = change.note.as_ref().map(|n| format!(" ({})", n)).unwrap_or_default();
+                text.push_str(&format!("  {}\n", style(format!("{} {}{}", change.kind, change.address, note)).dim()));
+            }
+        }
+
+        text
+    }
+
+    /// Every request of the plan, in the order they're sent.
+    fn steps(&self) -> Vec<(&Change, &Step)> {
+        let mut steps: Vec<(&Change, &Step)> = self.changes
+            .iter()
+            .flat_map(|c| c.steps.iter().map(move |s| (c, s)))
+            .collect();
+        steps.sort_by_key(|(_, s)| s.stage());
+        steps
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "summary": {
+                "create": self.count(Op::Create),
+                "update": self.count(Op::Update),
+                "delete": self.count(Op::Delete)
+            },
+            "changes": self.changes.iter().map(Change::to_json).collect::<Vec<_>>(),
+            "unmanaged": self.unmanaged.iter().map(Change::to_json).collect::<Vec<_>>()
+        })
+    }
+}
+
+/// Read and check a manifest. Every problem is reported at once.
+fn load(file: &str) -> Result<Manifest> {
+    let text = input::text(file)?;
+    let source = if file == "-" { "stdin" } else { file };
+    let manifest: Manifest = serde_yaml::from_str(&text)
+        .map_err(|e| CliError::new(ErrorKind::Validation, format!("Unable to parse {}: {}.", source, e)))?;
+
+    let mut errors = Vec::new();
+    let mut accounts = HashSet::new();
+    let mut installs = HashSet::new();
+
+    for account in &manifest.accounts {
+        if !accounts.insert(account.name.as_str()) {
+            errors.push(format!("account '{}' is in the manifest more than once.", account.name));
+        }
+
+        let mut sites = HashSet::new();
+        for site in account.sites.iter().flatten() {
+            if site.name.trim().is_empty() {
+                errors.push(format!("a site of account '{}' has no name.", account.name));
+            } else if !sites.insert(site.name.as_str()) {
+                errors.push(format!("site '{}/{}' is in the manifest more than once.", account.name, site.name));
+            }
+
+            for install in site.installs.iter().flatten() {
+                if !is_install_name(&install.name) {
+                    errors.push(format!("'{}' isn't a valid install name, use 3 to 14 lowercase letters and digits starting with a letter.", install.name));
+                }
+                if !installs.insert(install.name.as_str()) {
+                    errors.push(format!("install '{}' is in the manifest more than once.", install.name));
+                }
+                if Environment::from_str(&install.environment, true).is_err() {
+                    errors.push(format!("install '{}' has the environment '{}', use production, staging or development.", install.name, install.environment));
+                }
+
+                let domains: Vec<&str> = install.domains.iter().flatten().map(|d| d.name.as_str()).collect();
+                for (i, domain) in install.domains.iter().flatten().enumerate() {
+                    if domains[..i].iter().any(|d| d.eq_ignore_ascii_case(&domain.name)) {
+                        errors.push(format!("domain '{}/{}' is in the manifest more than once.", install.name, domain.name));
+                    }
+                    match &domain.redirect_to {
+                        Some(target) if target.eq_ignore_ascii_case(&domain.name) => {
+                            errors.push(format!("domain '{}/{}' redirects to itself.", install.name, domain.name));
+                        },
+                        Some(target) if !domains.iter().any(|d| d.eq_ignore_ascii_case(target)) => {
+                            errors.push(format!("domain '{}/{}' redirects to '{}', which isn't a domain of the install in the manifest.", install.name, domain.name, target));
+                        },
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        let mut users = HashSet::new();
+        for user in account.users.iter().flatten() {
+            if !users.insert(user.email.to_lowercase()) {
+                errors.push(format!("user '{}' of account '{}' is in the manifest more than once.", user.email, account.name));
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(CliError::new(
+            ErrorKind::Validation,
+            format!("{} problem(s) found in {}:\n  {}", errors.len(), source, errors.join("\n  "))
+        ).into());
+    }
+
+    Ok(manifest)
+}
+
+fn name_of(value: &Value) -> &str {
+    value["name"].as_str().unwrap_or_default()
+}
+
+fn id_of(value: &Value) -> String {
+    value["id"].as_str().unwrap_or_default().to_string()
+}
+
+/// The live state of an account, fetched before anything is compared.
+#[derive(Default)]
+struct LiveAccount {
+    id: String,
+    sites: Vec<Value>,
+    installs: Vec<Value>,
+    /// Domains by install ID, only of the installs whose domains the manifest manages.
+    domains: HashMap<String, Vec<Value>>,
+    /// Only fetched when the manifest manages the users of the account.
+    users: Vec<Value>
+}
+
+impl LiveAccount {
+    fn fetch(api: &API, spec: &AccountSpec) -> Result<Self> {
+        let id = api.resolve(Resource::Account, &spec.name)?;
+        let installs = api.get_all_installs(Some(&id))?;
+
+        let mut domains = HashMap::new();
+        for install in spec.sites.iter().flatten().flat_map(|s| s.installs.iter().flatten()) {
+            if let (Some(_), Some(live)) = (&install.domains, installs.iter().find(|i| name_of(i) == install.name)) {
+                domains.insert(id_of(live), api.get_all_domains(&id_of(live))?);
+            }
+        }
+
+        Ok(Self {
+            sites: api.get_all_sites(Some(&id))?,
+            users: if spec.users.is_some() { api.get_all_account_users(&id)? } else { Vec::new() },
+            id,
+            installs,
+            domains
+        })
+    }
+}
+
+/// Compares the manifest with the live state of its accounts.
+struct Planner {
+    /// Every install named in the manifest, they're never pruned from another site.
+    declared: HashSet<String>,
+    changes: Vec<Change>,
+    unmanaged: Vec<Change>,
+    errors: Vec<String>
+}
+
+impl Planner {
+    /// Keep a delete for `--prune`.
+    fn delete(&mut self, change: Change) {
+        self.unmanaged.push(change);
+    }
+
+    fn account(&mut self, spec: &AccountSpec, account: &LiveAccount) {
+        let (account_id, live_sites, live_installs) = (&account.id, &account.sites, &account.installs);
+        let site_names: HashMap<String, String> = live_sites.iter().map(|s| (id_of(s), name_of(s).to_string())).collect();
+
+        // Installs users can be given access to, whether they exist or are created first.
+        let mut installs: HashMap<String, Ref> = live_installs.iter().map(|i| (name_of(i).to_string(), Ref::Id(id_of(i)))).collect();
+
+        if let Some(sites) = &spec.sites {
+            for site in sites {
+                let address = format!("{}/{}", spec.name, site.name);
+                let live = live_sites.iter().find(|s| name_of(s) == site.name);
+                let site_ref = match live {
+                    Some(live) => Ref::Id(id_of(live)),
+                    None => {
+                        let step = Step::CreateSite {
+                            address: address.clone(),
+                            site: Site { name: site.name.clone(), account_id: account_id.clone() }
+                        };
+                        let mut change = Change::new(Op::Create, "site", address.clone(), vec![step]);
+                        change.field("name", Value::Null, json!(site.name));
+                        self.changes.push(change);
+                        Ref::New(address.clone())
+                    }
+                };
+
+                for install in site.installs.iter().flatten() {
+                    let install_ref = self.install(install, &site.name, &site_ref, account, &site_names);
+                    installs.insert(install.name.clone(), install_ref);
+                }
+
+                // Installs of the site that aren't anywhere in the manifest.
+                if let (Some(live), Some(_)) = (live, &site.installs) {
+                    for install in live_installs.iter().filter(|i| i["site"]["id"] == live["id"]) {
+                        if !self.declared.contains(name_of(install)) {
+                            let change = self.install_delete(install);
+                            self.delete(change);
+                        }
+                    }
+                }
+            }
+
+            for live in live_sites.iter().filter(|l| !sites.iter().any(|s| s.name == name_of(l))) {
+                let address = format!("{}/{}", spec.name, name_of(live));
+                let mut change = Change::new(Op::Delete, "site", address, vec![Step::DeleteSite { id: id_of(live) }]);
+                // Installs moved to another site in the manifest are moved before it is deleted.
+                let going: Vec<&Value> = live_installs
+                    .iter()
+                    .filter(|i| i["site"]["id"] == live["id"] && !self.declared.contains(name_of(i)))
+                    .collect();
+                change.production = going.iter().any(|i| i["environment"] == "production");
+                if !going.is_empty() {
+                    let names: Vec<&str> = going.iter().map(|i| name_of(i)).collect();
+                    change.note = Some(format!("with installs {}", names.join(", ")));
+                }
+                self.delete(change);
+            }
+        }
+
+        if let Some(users) = &spec.users {
+            self.users(users, account, &installs);
+        }
+    }
+
+    fn install_delete(&self, install: &Value) -> Change {
+        let mut change = Change::new(Op::Delete, "install", name_of(install).to_string(), vec![Step::DeleteInstall { id: id_of(install) }]);
+        change.production = install["environment"] == "production";
+        if change.production {
+            change.note = Some("production".to_string());
+        }
+        change
+    }
+
+    fn install(
+        &mut self,
+        spec: &InstallSpec,
+        site_name: &str,
+        site_ref: &Ref,
+        account: &LiveAccount,
+        site_names: &HashMap<String, String>
+    ) -> Ref {
+        let environment = spec.environment.to_lowercase();
+        let live = account.installs.iter().find(|i| name_of(i) == spec.name);
+
+        let install_ref = match live {
+            None => {
+                let step = Step::CreateInstall {
+                    address: spec.name.clone(),
+                    name: spec.name.clone(),
+                    account_id: account.id.clone(),
+                    site: site_ref.clone(),
+                    environment: environment.clone()
+                };
+                let mut change = Change::new(Op::Create, "install", spec.name.clone(), vec![step]);
+                change.field("site", Value::Null, json!(site_name));
+                change.field("environment", Value::Null, json!(environment));
+                self.changes.push(change);
+                Ref::New(spec.name.clone())
+            },
+            Some(live) => {
+                let id = id_of(live);
+                let live_site = live["site"]["id"].as_str().unwrap_or_default();
+                let moved = match site_ref {
+                    Ref::Id(site_id) => site_id != live_site,
+                    Ref::New(_) => true
+                };
+                let changed = live["environment"].as_str() != Some(environment.as_str());
+
+                if moved || changed {
+                    let step = Step::UpdateInstall {
+                        id: id.clone(),
+                        site: moved.then(|| site_ref.clone()),
+                        environment: changed.then(|| environment.clone())
+                    };
+                    let mut change = Change::new(Op::Update, "install", spec.name.clone(), vec![step]);
+                    if moved {
+                        let before = site_names.get(live_site).map(String::as_str).unwrap_or(live_site);
+                        change.field("site", json!(before), json!(site_name));
+                    }
+                    if changed {
+                        change.field("environment", live["environment"].clone(), json!(environment));
+                    }
+                    self.changes.push(change);
+                }
+                Ref::Id(id)
+            }
+        };
+
+        if let Some(domains) = &spec.domains {
+            let live_id = live.map(id_of);
+            let live_domains = live_id.as_ref().and_then(|id| account.domains.get(id)).map(Vec::as_slice).unwrap_or_default();
+            self.domains(domains, &spec.name, &install_ref, live_id, live_domains);
+        }
+
+        install_ref
+    }
+
+    fn domains(&mut self, specs: &[DomainSpec], install: &str, install_ref: &Ref, live_id: Option<String>, live_domains: &[Value]) {
+        let find = |name: &str| live_domains.iter().find(|d| name_of(d).eq_ignore_ascii_case(name));
+        let address = |name: &str| format!("{}/{}", install, name);
+
+        for spec in specs {
+            let redirect = spec.redirect_to.as_ref().map(|target| match find(target) {
+                Some(live) => Ref::Id(id_of(live)),
+                None => Ref::New(address(target))
+            });
+
+            match find(&spec.name) {
+                None => {
+                    let mut steps = vec![Step::CreateDomain {
+                        address: address(&spec.name),
+                        install: install_ref.clone(),
+                        domain: Domain { name: spec.name.clone(), primary: spec.primary.unwrap_or(false) }
+                    }];
+                    // A redirect is set once the domains it points to exist.
+                    if redirect.is_some() {
+                        steps.push(Step::UpdateDomain {
+                            install: install_ref.clone(),
+                            domain: Ref::New(address(&spec.name)),
+                            primary: None,
+                            redirect_to: redirect
+                        });
+                    }
+                    let mut change = Change::new(Op::Create, "domain", address(&spec.name), steps);
+                    change.field("primary", Value::Null, json!(spec.primary.unwrap_or(false)));
+                    if let Some(target) = &spec.redirect_to {
+                        change.field("redirect_to", Value::Null, json!(target));
+                    }
+                    self.changes.push(change);
+                },
+                Some(live) => {
+                    let primary = spec.primary.filter(|p| live["primary"].as_bool() != Some(*p));
+                    // The redirect is shown by name, the API gives it as a domain ID.
+                    let current = live["redirects_to"][0]["id"]
+                        .as_str()
+                        .map(|id| live_domains.iter().find(|d| d["id"] == id).map(name_of).unwrap_or(id));
+                    let target = spec.redirect_to.as_deref().filter(|t| !current.is_some_and(|c| c.eq_ignore_ascii_case(t)));
+
+                    if primary.is_some() || target.is_some() {
+                        let step = Step::UpdateDomain {
+                            install: install_ref.clone(),
+                            domain: Ref::Id(id_of(live)),
+                            primary,
+                            redirect_to: target.and(redirect)
+                        };
+                        let mut change = Change::new(Op::Update, "domain", address(&spec.name), vec![step]);
+                        if let Some(primary) = primary {
+                            change.field("primary", live["primary"].clone(), json!(primary));
+                        }
+                        if let Some(target) = target {
+                            change.field("redirect_to", json!(current), json!(target));
+                        }
+                        self.changes.push(change);
+                    }
+                }
+            }
+        }
+
+        if let Some(install_id) = live_id {
+            for live in live_domains.iter().filter(|l| !specs.iter().any(|s| s.name.eq_ignore_ascii_case(name_of(l)))) {
+                let step = Step::DeleteDomain { install_id: install_id.clone(), id: id_of(live) };
+                self.delete(Change::new(Op::Delete, "domain", address(name_of(live)), vec![step]));
+            }
+        }
+    }
+
+    fn users(&mut self, specs: &[UserSpec], account: &LiveAccount, installs: &HashMap<String, Ref>) {
+        let (account_id, live_users) = (&account.id, &account.users);
+        let install_names: HashMap<String, &str> = account.installs.iter().map(|i| (id_of(i), name_of(i))).collect();
+
+        for spec in specs {
+            let refs = match &spec.installs {
+                Some(names) => {
+                    let mut refs = Vec::new();
+                    for name in names {
+                        match installs.get(name) {
+                            Some(install) => refs.push(install.clone()),
+                            None => self.errors.push(format!("user '{}' has access to '{}', which isn't an install of the account.", spec.email, name))
+                        }
+                    }
+                    Some(refs)
+                },
+                None => None
+            };
+            let mut wanted: Vec<String> = spec.installs.clone().unwrap_or_default();
+            wanted.sort();
+
+            match live_users.iter().find(|u| u["email"].as_str().is_some_and(|e| e.eq_ignore_ascii_case(&spec.email))) {
+                None => {
+                    let (Some(first_name), Some(last_name), Some(roles)) = (&spec.first_name, &spec.last_name, &spec.roles) else {
+                        self.errors.push(format!("user '{}' is new, so it needs first_name, last_name and roles.", spec.email));
+                        continue;
+                    };
+                    let step = Step::CreateUser {
+                        account_id: account_id.to_string(),
+                        user: User {
+                            account_id: account_id.to_string(),
+                            first_name: first_name.clone(),
+                            last_name: last_name.clone(),
+                            email: spec.email.clone(),
+                            roles: roles.clone(),
+                            install_ids: Vec::new()
+                        },
+                        installs: refs.unwrap_or_default()
+                    };
+                    let mut change = Change::new(Op::Create, "user", spec.email.clone(), vec![step]);
+                    change.field("roles", Value::Null, json!(roles));
+                    if !wanted.is_empty() {
+                        change.field("installs", Value::Null, json!(wanted));
+                    }
+                    self.changes.push(change);
+                },
+                Some(live) => {
+                    let roles = spec.roles.as_ref().filter(|r| live["roles"].as_str() != Some(r.as_str()));
+                    let mut current: Vec<String> = live["installs"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .map(|i| match i["name"].as_str() {
+                            Some(name) => name.to_string(),
+                            None => {
+                                let id = i["id"].as_str().or(i.as_str()).unwrap_or_default();
+                                install_names.get(id).map(|n| n.to_string()).unwrap_or_else(|| id.to_string())
+                            }
+                        })
+                        .collect();
+                    current.sort();
+                    let access = refs.filter(|_| current != wanted);
+
+                    if roles.is_some() || access.is_some() {
+                        let step = Step::UpdateUser {
+                            account_id: account_id.to_string(),
+                            id: live["user_id"].as_str().unwrap_or_default().to_string(),
+                            roles: roles.cloned(),
+                            installs: access.clone()
+                        };
+                        let mut change = Change::new(Op::Update, "user", spec.email.clone(), vec![step]);
+                        if let Some(roles) = roles {
+                            change.field("roles", live["roles"].clone(), json!(roles));
+                        }
+                        if access.is_some() {
+                            change.field("installs", json!(current), json!(wanted));
+                        }
+                        self.changes.push(change);
+                    }
+                }
+            }
+        }
+
+        for live in live_users.iter().filter(|l| {
+            !specs.iter().any(|s| l["email"].as_str().is_some_and(|e| e.eq_ignore_ascii_case(&s.email)))
+        }) {
+            let step = Step::DeleteUser {
+                account_id: account_id.to_string(),
+                id: live["user_id"].as_str().unwrap_or_default().to_string()
+            };
+            let email = live["email"].as_str().unwrap_or_default().to_string();
+            self.delete(Change::new(Op::Delete, "user", email, vec![step]));
+        }
+    }
+}
+
+/// Compare a manifest with the live state.
+fn build(file: &str, prune: bool, api: &API) -> Result<Plan> {
+    let manifest = load(file)?;
+    let live = manifest.accounts
+        .iter()
+        .map(|account| LiveAccount::fetch(api, account))
+        .collect::<Result<Vec<_>>>()?;
+
+    compare(&manifest, &live, prune).map_err(|errors| CliError::new(
+        ErrorKind::Validation,
+        format!("{} problem(s) found in {}:\n  {}", errors.len(), if file == "-" { "stdin" } else { file }, errors.join("\n  "))
+    ).into())
+}
+
+/// Compare a manifest with the live state of each of its accounts, or list the
+/// problems that keep it from being planned.
+fn compare(manifest: &Manifest, live: &[LiveAccount], prune: bool) -> std::result::Result<Plan, Vec<String>> {
+    let declared = manifest.accounts
+        .iter()
+        .flat_map(|a| a.sites.iter().flatten())
+        .flat_map(|s| s.installs.iter().flatten())
+        .map(|i| i.name.clone())
+        .collect();
+
+    let mut planner = Planner { declared, changes: Vec::new(), unmanaged: Vec::new(), errors: Vec::new() };
+    for (spec, account) in manifest.accounts.iter().zip(live) {
+        planner.account(spec, account);
+    }
+
+    if !planner.errors.is_empty() {
+        return Err(planner.errors);
+    }
+
+    let mut plan = Plan { changes: planner.changes, unmanaged: planner.unmanaged };
+    if prune {
+        plan.changes.append(&mut plan.unmanaged);
+    }
+
+    // Shown in the order they're applied.
+    plan.changes.sort_by_key(|c| c.steps.first().map(Step::stage));
+
+    Ok(plan)
+}
+
+/// Provides logic for the plan command.
+///
+/// # Arguments
+///
+/// * `file` - &str
+/// * `prune` - bool
+/// * `api` - &API
+/// * `out` - &Output
+pub fn plan(file: &str, prune: bool, api: &API, out: &Output) -> Result<()> {
+    let plan = build(file, prune, api)?;
+
+    // The drawn plan only stands in for a table, other formats get the changes as data.
+    if out.format != Format::Table || out.query.is_some() || out.fields.is_some() {
+        return out.print(&plan.to_json(), Kind::Other);
+    }
+
+    output::write_stdout(&plan.render())
+}
+
+/// Provides logic for the apply command.
+///
+/// # Arguments
+///
+/// * `file` - &str
+/// * `prune` - bool
+/// * `confirm` - &Confirmation
+/// * `api` - &API
+/// * `headless` - bool
+pub fn apply(file: &str, prune: bool, confirm: &Confirmation, api: &API, headless: bool) -> Result<()> {
+    let plan = build(file, prune, api)?;
+    eprint!("{}", plan.render());
+    if plan.changes.is_empty() {
+        return Ok(());
+    }
+
+    if !confirm.force_production && plan.changes.iter().any(|c| c.production) {
+        return Err(CliError::new(
+            ErrorKind::Usage,
+            "The plan deletes a production install, pass --force-production to apply it."
+        ).into());
+    }
+    if !confirm.yes {
+        if headless {
+            return Err(CliError::new(ErrorKind::Usage, "Pass --yes to apply the plan in headless mode.").into());
+        }
+        if !Confirm::new().with_prompt("Apply these changes?").default(false).interact()? {
+            eprintln!("Cancelled, nothing was applied.");
+            return Ok(());
+        }
+    }
+
+    let steps = plan.steps();
+    let mut ids = HashMap::new();
+    for (i, (change, step)) in steps.iter().enumerate() {
+        step.run(api, &mut ids)
+            .map_err(|e| e.context(format!("Stopped at {} {} after {} of {} requests", change.kind, change.address, i, steps.len())))?;
+
+        let done = match step {
+            Step::UpdateDomain { .. } if change.op == Op::Create => "configured",
+            _ => match change.op {
+                Op::Create => "created",
+                Op::Update => "updated",
+                Op::Delete => "deleted"
+            }
+        };
+        eprintln!("{} {} {}", style(done).bold(), change.kind, change.address);
+    }
+
+    eprintln!("Apply complete: {}", plan.summary().replace("to add", "added").replace("to change", "changed").replace("to delete", "deleted"));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Account acme with sites Acme and Old, and a user with access to acmestg.
+    fn live() -> Vec<LiveAccount> {
+        let install = |id: &str, name: &str, environment: &str, site: &str| json!({
+            "id": id, "name": name, "environment": environment, "site": { "id": site }
+        });
+        let domain = |id: &str, name: &str, primary: bool| json!({
+            "id": id, "name": name, "primary": primary, "redirects_to": []
+        });
+
+        vec![LiveAccount {
+            id: "acc1".to_string(),
+            sites: vec![json!({ "id": "s1", "name": "Acme" }), json!({ "id": "s2", "name": "Old" })],
+            installs: vec![
+                install("i1", "acmeprod", "production", "s1"),
+                install("i2", "acmestg", "staging", "s1"),
+                install("i3", "oldprod", "production", "s2")
+            ],
+            domains: HashMap::from([(
+                "i1".to_string(),
+                vec![domain("d1", "acme.com", true), domain("d2", "www.acme.com", false)]
+            )]),
+            users: vec![json!({
+                "user_id": "u1",
+                "email": "ann@acme.com",
+                "roles": "partial",
+                "installs": [{ "id": "i2", "name": "acmestg" }]
+            })]
+        }]
+    }
+
+    fn plan(manifest: &str, prune: bool) -> Plan {
+        let manifest: Manifest = serde_yaml::from_str(manifest).unwrap();
+        compare(&manifest, &live(), prune).unwrap()
+    }
+
+    fn addresses(changes: &[Change]) -> Vec<(Op, &str, &str)> {
+        changes.iter().map(|c| (c.op, c.kind, c.address.as_str())).collect()
+    }
+
+    /// The live state, less site Old and the users.
+    const CURRENT: &str = r#"
+accounts:
+  - name: acme
+    sites:
+      - name: Acme
+        installs:
+          - { name: acmeprod, environment: production, domains: [{ name: acme.com }, { name: www.acme.com }] }
+          - { name: acmestg, environment: staging }
+"#;
+
+    #[test]
+    fn no_changes_when_the_live_state_matches() {
+        let plan = plan(CURRENT, false);
+        assert!(plan.changes.is_empty());
+        assert_eq!(addresses(&plan.unmanaged), vec![(Op::Delete, "site", "acme/Old")]);
+    }
+
+    #[test]
+    fn creates_what_is_missing() {
+        let plan = plan(&format!("{}{}", CURRENT, r#"
+      - name: Shop
+        installs:
+          - { name: shopprod, environment: Production, domains: [{ name: shop.com, primary: true }] }
+"#), false);
+
+        assert_eq!(addresses(&plan.changes), vec![
+            (Op::Create, "site", "acme/Shop"),
+            (Op::Create, "install", "shopprod"),
+            (Op::Create, "domain", "shopprod/shop.com")
+        ]);
+        // The install goes on the site created before it.
+        assert!(matches!(
+            plan.changes[1].steps[..],
+            [Step::CreateInstall { site: Ref::New(ref site), ref environment, .. }] if site == "acme/Shop" && environment == "production"
+        ));
+    }
+
+    #[test]
+    fn updates_changed_fields_only() {
+        let manifest = CURRENT.replace("environment: staging", "environment: development") + r#"
+    users:
+      - { email: ANN@acme.com, installs: [acmestg, acmeprod] }
+"#;
+        let plan = plan(&manifest, false);
+
+        assert_eq!(addresses(&plan.changes), vec![(Op::Update, "install", "acmestg"), (Op::Update, "user", "ANN@acme.com")]);
+        let fields: Vec<(&str, &Value, &Value)> = plan.changes
+            .iter()
+            .flat_map(|c| c.fields.iter().map(|f| (f.field.as_str(), &f.before, &f.after)))
+            .collect();
+        assert_eq!(fields, vec![
+            ("environment", &json!("staging"), &json!("development")),
+            ("installs", &json!(["acmestg"]), &json!(["acmeprod", "acmestg"]))
+        ]);
+    }
+
+    #[test]
+    fn moves_an_install_to_another_site() {
+        let plan = plan(r#"
+accounts:
+  - name: acme
+    sites:
+      - name: Acme
+        installs: [{ name: acmeprod, environment: production }]
+      - name: Old
+        installs:
+          - { name: acmestg, environment: staging }
+          - { name: oldprod, environment: production }
+"#, false);
+
+        assert_eq!(addresses(&plan.changes), vec![(Op::Update, "install", "acmestg")]);
+        assert_eq!(plan.changes[0].fields[0].field, "site");
+        assert_eq!((&plan.changes[0].fields[0].before, &plan.changes[0].fields[0].after), (&json!("Acme"), &json!("Old")));
+        assert!(matches!(
+            plan.changes[0].steps[..],
+            [Step::UpdateInstall { site: Some(Ref::Id(ref site)), environment: None, .. }] if site == "s2"
+        ));
+    }
+
+    #[test]
+    fn sets_the_new_primary_domain_first() {
+        let manifest = CURRENT.replace(
+            "[{ name: acme.com }, { name: www.acme.com }]",
+            "[{ name: acme.com, primary: false, redirect_to: www.acme.com }, { name: www.acme.com, primary: true }]"
+        );
+        let plan = plan(&manifest, false);
+
+        let domains: Vec<(&str, Option<bool>)> = plan.steps()
+            .into_iter()
+            .filter_map(|(_, step)| match step {
+                Step::UpdateDomain { domain: Ref::Id(id), primary, .. } => Some((id.as_str(), *primary)),
+                _ => None
+            })
+            .collect();
+        assert_eq!(domains, vec![("d2", Some(true)), ("d1", Some(false))]);
+    }
+
+    #[test]
+    fn prunes_only_when_asked() {
+        let manifest = CURRENT.replace("          - { name: acmestg, environment: staging }\n", "") + "    users: []\n";
+
+        let plan_only = plan(&manifest, false);
+        assert!(plan_only.changes.is_empty());
+        assert_eq!(plan_only.unmanaged.len(), 3);
+
+        let pruned = plan(&manifest, true);
+        assert!(pruned.unmanaged.is_empty());
+        assert_eq!(addresses(&pruned.changes), vec![
+            (Op::Delete, "user", "ann@acme.com"),
+            (Op::Delete, "install", "acmestg"),
+            (Op::Delete, "site", "acme/Old")
+        ]);
+        let site = &pruned.changes[2];
+        assert!(site.production);
+        assert_eq!(site.note.as_deref(), Some("with installs oldprod"));
+    }
+
+    #[test]
+    fn lists_every_problem_with_the_live_state() {
+        let manifest: Manifest = serde_yaml::from_str(&(CURRENT.to_string() + r#"
+    users:
+      - { email: bob@acme.com, roles: full }
+      - { email: ann@acme.com, installs: [nowhere] }
+"#)).unwrap();
+        let errors = compare(&manifest, &live(), false).err().unwrap();
+
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("needs first_name, last_name and roles"));
+        assert!(errors[1].contains("'nowhere'"));
+    }
+}
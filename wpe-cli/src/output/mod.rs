@@ -0,0 +1,485 @@
+use std::io::{IsTerminal, Write};
+use clap::ValueEnum;
+use comfy_table::{Table, presets::UTF8_FULL_CONDENSED, ContentArrangement};
+use serde::Serialize;
+use serde_json::{json, Value};
+use serde_json_path::JsonPath;
+use anyhow::Result;
+
+/// Formats that command output can be rendered in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Table,
+    Json,
+    Yaml,
+    Csv,
+    Ndjson
+}
+
+/// The shape of the data being printed, used to pick table and csv columns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Sites,
+    Installs,
+    Domains,
+    Accounts,
+    Users,
+    SshKeys,
+    Backups,
+    Other
+}
+
+impl Kind {
+    /// Column headers and the JSON pointer each one is read from.
+    fn columns(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Kind::Sites => &[
+                ("ID", "/id"),
+                ("NAME", "/name"),
+                ("ACCOUNT", "/account/id"),
+                ("INSTALLS", "/installs")
+            ],
+            Kind::Installs => &[
+                ("ID", "/id"),
+                ("NAME", "/name"),
+                ("ENVIRONMENT", "/environment"),
+                ("PHP", "/php_version"),
+                ("CNAME", "/cname")
+            ],
+            Kind::Domains => &[
+                ("ID", "/id"),
+                ("NAME", "/name"),
+                ("PRIMARY", "/primary"),
+                ("DUPLICATE", "/duplicate")
+            ],
+            Kind::Accounts => &[
+                ("ID", "/id"),
+                ("NAME", "/name")
+            ],
+            Kind::Users => &[
+                ("ID", "/user_id"),
+                ("EMAIL", "/email"),
+                ("FIRST NAME", "/first_name"),
+                ("LAST NAME", "/last_name"),
+                ("ROLES", "/roles")
+            ],
+            Kind::SshKeys => &[
+                ("ID", "/uuid"),
+                ("COMMENT", "/comment"),
+                ("FINGERPRINT", "/fingerprint"),
+                ("CREATED", "/created_at")
+            ],
+            Kind::Backups => &[
+                ("ID", "/id"),
+                ("INSTALL", "/install_id"),
+                ("DESCRIPTION", "/description"),
+                ("CREATED", "/created_at"),
+                ("STATUS", "/status"),
+                ("ERROR", "/error")
+            ],
+            Kind::Other => &[]
+        }
+    }
+}
+
+/// A parsed `--query` expression.
+pub struct Query {
+    path: JsonPath,
+    singular: bool
+}
+
+impl Query {
+    /// Parse a JSONPath expression, also accepting jq-style paths like `.results[].name`.
+    pub fn parse(expression: &str) -> Result<Self> {
+        let expression = expression.trim().replace("[]", "[*]");
+        let expression = if expression.starts_with('$') {
+            expression
+        } else if expression.starts_with('.') || expression.starts_with('[') {
+            format!("${}", expression)
+        } else {
+            format!("$.{}", expression)
+        };
+        // jq writes `.[0]` where JSONPath expects `[0]`, leaving descendant `..[` alone.
+        let expression = expression
+            .replace("..[", "\0")
+            .replace(".[", "[")
+            .replace('\0', "..[");
+
+        let path = JsonPath::parse(&expression)
+            .map_err(|e| wpe::CliError::new(wpe::ErrorKind::Usage, format!("Invalid query '{}': {}", expression, e)))?;
+
+        // Paths without wildcards, filters, slices or unions can only ever
+        // match one value, so print that value rather than a list of one.
+        let singular = !["*", "..", "?", ":", ","]
+            .iter()
+            .any(|token| expression.contains(token));
+
+        Ok(Self { path, singular })
+    }
+
+    fn apply(&self, value: &Value) -> Value {
+        let nodes = self.path.query(value).all();
+        if self.singular {
+            nodes.first().map(|v| (*v).clone()).unwrap_or(Value::Null)
+        } else {
+            Value::Array(nodes.into_iter().cloned().collect())
+        }
+    }
+}
+
+/// Output settings shared by every command.
+pub struct Output {
+    pub format: Format,
+    pub fields: Option<Vec<String>>,
+    pub query: Option<Query>
+}
+
+impl Output {
+    /// Build from the global `--output`, `--fields` and `--query` flags.
+    /// Format defaults to a table on a terminal and JSON when piped.
+    pub fn new(format: Option<Format>, fields: &[String], query: Option<&str>) -> Result<Self> {
+        let format = format.unwrap_or(if std::io::stdout().is_terminal() {
+            Format::Table
+        } else {
+            Format::Json
+        });
+
+        let fields = (!fields.is_empty()).then(|| fields.iter().map(|s| s.trim().to_string()).collect());
+
+        let query = query.map(Query::parse).transpose()?;
+
+        Ok(Self { format, fields, query })
+    }
+
+    /// Render any serializable response in the selected format.
+    pub fn print<T: Serialize>(&self, data: &T, kind: Kind) -> Result<()> {
+        let mut value = serde_json::to_value(data)?;
+        // Empty responses, like a 204 or a dry run's, have nothing to show.
+        if value.is_null() {
+            return Ok(());
+        }
+        let mut kind = kind;
+
+        // A query can reshape the data entirely, so the kind's columns no longer apply.
+        if let Some(query) = &self.query {
+            value = query.apply(&value);
+            kind = Kind::Other;
+        }
+        if let Some(fields) = &self.fields {
+            value = project(&value, fields);
+        }
+        let fields = self.fields.as_deref();
+
+        let rendered = match self.format {
+            Format::Json => format!("{}\n", serde_json::to_string_pretty(&value)?),
+            Format::Yaml => serde_yaml::to_string(&value)?,
+            Format::Ndjson => {
+                let mut lines = String::new();
+                for row in rows(&value) {
+                    lines.push_str(&serde_json::to_string(row)?);
+                    lines.push('\n');
+                }
+                lines
+            },
+            Format::Csv => to_csv(&value, kind, fields)?,
+            Format::Table => {
+                if value.is_object() && !value["results"].is_array() && fields.is_none() {
+                    // A single resource reads better as field/value pairs.
+                    format!("{}\n", detail_table(&value))
+                } else {
+                    let (headers, cells) = tabulate(&value, kind, fields);
+                    let mut table = new_table();
+                    table.set_header(headers);
+                    for row in cells {
+                        table.add_row(row);
+                    }
+                    format!("{table}\n")
+                }
+            }
+        };
+
+        write_stdout(&rendered)
+    }
+}
+
+/// Print an error to stderr, as a JSON object when JSON output was asked for so
+/// pipelines can branch on its kind. Returns the exit code for the error.
+pub fn print_error(err: &anyhow::Error, format: Option<Format>) -> i32 {
+    let kind = wpe::CliError::kind_of(err);
+    let status = err.chain().find_map(|e| e.downcast_ref::<wpe::CliError>()).and_then(|e| e.status);
+
+    if matches!(format, Some(Format::Json | Format::Ndjson)) {
+        let error = json!({
+            "error": {
+                "kind": kind,
+                "exit_code": kind.exit_code(),
+                "status": status,
+                "message": format!("{:#}", err)
+            }
+        });
+        eprintln!("{}", error);
+    } else {
+        eprintln!("Error: {:#}", err);
+    }
+
+    kind.exit_code()
+}
+
+/// Write to stdout, treating a closed pipe (e.g. `| head`) as success.
+pub fn write_stdout(text: &str) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    match stdout.write_all(text.as_bytes()).and_then(|_| stdout.flush()) {
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        res => Ok(res?)
+    }
+}
+
+fn new_table() -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL_CONDENSED)
+        .set_content_arrangement(ContentArrangement::Dynamic);
+    table
+}
+
+/// Listings come back either bare or wrapped in a paginated `results` array.
+fn rows(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(items) => items.iter().collect(),
+        Value::Object(map) => match map.get("results") {
+            Some(Value::Array(items)) => items.iter().collect(),
+            _ => vec![value]
+        },
+        Value::Null => vec![],
+        _ => vec![value]
+    }
+}
+
+/// Turn a field name like `account.id` into a JSON pointer.
+fn pointer(field: &str) -> String {
+    format!("/{}", field.replace('.', "/"))
+}
+
+/// Keep only the selected fields of each row. Listings are unwrapped from
+/// their `results` so the selected fields sit at the top level.
+fn project(value: &Value, fields: &[String]) -> Value {
+    let select = |item: &Value| -> Value {
+        if !item.is_object() {
+            return item.clone();
+        }
+        let mut map = serde_json::Map::new();
+        for field in fields {
+            map.insert(
+                field.to_string(),
+                item.pointer(&pointer(field)).cloned().unwrap_or(Value::Null)
+            );
+        }
+        Value::Object(map)
+    };
+
+    match value {
+        Value::Object(map) if !map.contains_key("results") => select(value),
+        Value::Object(_) | Value::Array(_) => Value::Array(rows(value).into_iter().map(select).collect()),
+        _ => value.clone()
+    }
+}
+
+/// Flatten a value into a single table or csv cell.
+fn cell(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.to_string(),
+        // Nested listings like a site's installs are summarised by their size.
+        Some(Value::Array(items)) => items.len().to_string(),
+        Some(v) => v.to_string()
+    }
+}
+
+/// Build headers and rows from the selected fields or the kind's column set,
+/// falling back to the top-level scalar fields of the data when the kind has none.
+fn tabulate(value: &Value, kind: Kind, fields: Option<&[String]>) -> (Vec<String>, Vec<Vec<String>>) {
+    let items = rows(value);
+
+    // Bare values, e.g. from `--query '$.results[*].name'`, get a single column.
+    if items.iter().all(|item| !item.is_object()) {
+        return (
+            vec!["VALUE".to_string()],
+            items.iter().map(|item| vec![cell(Some(item))]).collect()
+        );
+    }
+
+    let columns: Vec<(String, String)> = if let Some(fields) = fields {
+        // Projected rows are keyed by the field name itself.
+        fields
+            .iter()
+            .map(|f| (f.to_uppercase(), format!("/{}", f.replace('~', "~0").replace('/', "~1"))))
+            .collect()
+    } else if kind.columns().is_empty() {
+        let mut keys: Vec<String> = Vec::new();
+        for item in &items {
+            if let Value::Object(map) = item {
+                for (key, v) in map {
+                    if !v.is_object() && !keys.contains(key) {
+                        keys.push(key.to_string());
+                    }
+                }
+            }
+        }
+        keys.into_iter().map(|k| (k.to_uppercase(), format!("/{}", k))).collect()
+    } else {
+        kind.columns()
+            .iter()
+            .map(|(header, pointer)| (header.to_string(), pointer.to_string()))
+            .collect()
+    };
+
+    let headers = columns.iter().map(|(h, _)| h.clone()).collect();
+    let cells = items
+        .iter()
+        .map(|item| columns.iter().map(|(_, p)| cell(item.pointer(p))).collect())
+        .collect();
+
+    (headers, cells)
+}
+
+/// Render rows as csv, quoting cells with commas, quotes or line breaks.
+fn to_csv(value: &Value, kind: Kind, fields: Option<&[String]>) -> Result<String> {
+    let (headers, cells) = tabulate(value, kind, fields);
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(&headers)?;
+    for row in cells {
+        writer.write_record(&row)?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+fn detail_table(value: &Value) -> Table {
+    let mut table = new_table();
+    if let Value::Object(map) = value {
+        for (key, v) in map {
+            let text = match v {
+                Value::String(s) => s.to_string(),
+                Value::Null => String::new(),
+                v => v.to_string()
+            };
+            table.add_row(vec![key.to_string(), text]);
+        }
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn query(expression: &str) -> Value {
+        let data = json!({
+            "results": [
+                { "id": "a", "name": "acmeprod", "environment": "production" },
+                { "id": "b", "name": "acmestg", "environment": "staging" }
+            ]
+        });
+        Query::parse(expression).unwrap().apply(&data)
+    }
+
+    fn installs() -> Value {
+        json!({
+            "results": [
+                {
+                    "id": "a",
+                    "name": "acmeprod",
+                    "environment": "production",
+                    "php_version": "8.2",
+                    "cname": "acmeprod.wpengine.com",
+                    "account": { "id": "acc1" }
+                },
+                { "id": "b", "name": "acmestg", "environment": "staging", "account": { "id": "acc1" } }
+            ]
+        })
+    }
+
+    #[test]
+    fn kinds_pick_their_columns() {
+        let (headers, cells) = tabulate(&installs(), Kind::Installs, None);
+        assert_eq!(headers, vec!["ID", "NAME", "ENVIRONMENT", "PHP", "CNAME"]);
+        assert_eq!(cells[1], vec!["b", "acmestg", "staging", "", ""]);
+
+        let site = json!([{ "id": "s1", "name": "Acme", "account": { "id": "acc1" }, "installs": [{}, {}] }]);
+        let (headers, cells) = tabulate(&site, Kind::Sites, None);
+        assert_eq!(headers, vec!["ID", "NAME", "ACCOUNT", "INSTALLS"]);
+        assert_eq!(cells[0], vec!["s1", "Acme", "acc1", "2"]);
+    }
+
+    #[test]
+    fn other_data_shows_its_top_level_scalars() {
+        let (headers, cells) = tabulate(&installs(), Kind::Other, None);
+        assert_eq!(headers, vec!["ID", "NAME", "ENVIRONMENT", "PHP_VERSION", "CNAME"]);
+        assert_eq!(cells[1], vec!["b", "acmestg", "staging", "", ""]);
+
+        let (headers, cells) = tabulate(&json!(["a", "b"]), Kind::Other, None);
+        assert_eq!((headers, cells), (vec!["VALUE".to_string()], vec![vec!["a".to_string()], vec!["b".to_string()]]));
+    }
+
+    #[test]
+    fn fields_select_dotted_paths() {
+        let fields = vec!["name".to_string(), "account.id".to_string()];
+        let projected = project(&installs(), &fields);
+        assert_eq!(projected[0], json!({ "name": "acmeprod", "account.id": "acc1" }));
+
+        // A single resource stays an object, missing fields are null.
+        let single = project(&json!({ "name": "acmeprod" }), &fields);
+        assert_eq!(single, json!({ "name": "acmeprod", "account.id": null }));
+
+        let (headers, cells) = tabulate(&projected, Kind::Installs, Some(&fields));
+        assert_eq!(headers, vec!["NAME", "ACCOUNT.ID"]);
+        assert_eq!(cells, vec![vec!["acmeprod", "acc1"], vec!["acmestg", "acc1"]]);
+    }
+
+    #[test]
+    fn csv_quotes_commas_quotes_and_line_breaks() {
+        let data = json!([{ "id": "1", "description": "before, \"big\" update\nand after" }]);
+        let csv = to_csv(&data, Kind::Other, None).unwrap();
+        assert_eq!(csv, "ID,DESCRIPTION\n1,\"before, \"\"big\"\" update\nand after\"\n");
+    }
+
+    #[test]
+    fn jq_paths_are_rewritten_to_jsonpath() {
+        assert_eq!(query(".results[0].name"), json!("acmeprod"));
+        assert_eq!(query("results[1].id"), json!("b"));
+        assert_eq!(query(".results[].id"), json!(["a", "b"]));
+    }
+
+    #[test]
+    fn jq_array_index_on_the_root() {
+        let list = json!([{ "name": "first" }, { "name": "second" }]);
+
+        assert_eq!(Query::parse(".[0]").unwrap().apply(&list), json!({ "name": "first" }));
+        assert_eq!(Query::parse(".[1].name").unwrap().apply(&list), json!("second"));
+        assert_eq!(Query::parse(".[]").unwrap().apply(&list).as_array().map(Vec::len), Some(2));
+    }
+
+    #[test]
+    fn descendant_queries_print_a_list() {
+        assert_eq!(query("$..name"), json!(["acmeprod", "acmestg"]));
+        assert_eq!(query("$..[0].id"), json!(["a"]));
+    }
+
+    #[test]
+    fn filters_and_slices_print_a_list_even_for_one_match() {
+        assert_eq!(query("$.results[?@.environment=='production'].name"), json!(["acmeprod"]));
+        assert_eq!(query("$.results[0:1].id"), json!(["a"]));
+    }
+
+    #[test]
+    fn a_single_path_without_a_match_is_null() {
+        assert_eq!(query(".results[5].name"), Value::Null);
+        assert_eq!(query(".missing"), Value::Null);
+    }
+
+    #[test]
+    fn invalid_queries_are_usage_errors() {
+        let err = Query::parse("$.results[").err().unwrap();
+        assert_eq!(wpe::CliError::kind_of(&err), wpe::ErrorKind::Usage);
+    }
+}
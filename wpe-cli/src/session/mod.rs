@@ -0,0 +1,625 @@
+use std::collections::HashMap;
+use dialoguer::{
+    Select,
+    Input,
+    theme::ColorfulTheme
+};
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use wpe::*;
+use crate::output::{Output, Kind};
+use crate::filter::Criteria;
+use crate::sites::site_picker;
+use crate::bulk;
+use crate::guard::{self, Guard, Impact};
+use crate::diff;
+use crate::cli::{CacheType, Environment};
+
+const BACK: &str = "« Back";
+const MAIN_MENU: &str = "⌂ Main menu";
+const CANCEL: &str = "✕ Cancel";
+
+/// A place in the interactive session. Detail screens carry the ID they show.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Screen {
+    Main,
+    Sites,
+    Site(String),
+    Installs,
+    Install(String),
+    Domains(String),
+    Accounts,
+    Account(String),
+    Users(String)
+}
+
+/// Where to go after a screen has been handled.
+enum Nav {
+    Push(Screen),
+    Stay,
+    Back,
+    Main,
+    Quit
+}
+
+/// What was picked from a screen's menu.
+enum Choice {
+    Item(usize),
+    Nav(Nav)
+}
+
+/// An interactive session that moves between screens on a stack and keeps
+/// whatever it has fetched, so going back never refetches.
+pub struct Session<'a> {
+    api: &'a API,
+    out: &'a Output,
+    account: Option<&'a str>,
+    site_criteria: Criteria,
+    install_criteria: Criteria,
+    /// Whether deletes may take production installs with them.
+    force_production: bool,
+    stack: Vec<Screen>,
+    sites: HashMap<String, Value>,
+    installs: HashMap<String, Value>,
+    accounts: HashMap<String, Value>,
+    domains: HashMap<String, Vec<Value>>,
+    users: HashMap<String, Vec<Value>>,
+    site_picker: Option<Picker<'a>>,
+    install_picker: Option<Picker<'a>>,
+    account_picker: Option<Picker<'a>>
+}
+
+impl<'a> Session<'a> {
+    pub fn new(api: &'a API, out: &'a Output, account: Option<&'a str>) -> Self {
+        Self {
+            api,
+            out,
+            account,
+            site_criteria: Criteria::default(),
+            install_criteria: Criteria::default(),
+            force_production: false,
+            stack: vec![Screen::Main],
+            sites: HashMap::new(),
+            installs: HashMap::new(),
+            accounts: HashMap::new(),
+            domains: HashMap::new(),
+            users: HashMap::new(),
+            site_picker: None,
+            install_picker: None,
+            account_picker: None
+        }
+    }
+
+    /// Filters and sorting for the sites picker.
+    pub fn site_criteria(mut self, criteria: Criteria) -> Self {
+        self.site_criteria = criteria;
+        self
+    }
+
+    /// Filters and sorting for the installs picker.
+    pub fn install_criteria(mut self, criteria: Criteria) -> Self {
+        self.install_criteria = criteria;
+        self
+    }
+
+    /// Allow deleting production installs, as `--force-production` does on the delete verbs.
+    pub fn force_production(mut self, force_production: bool) -> Self {
+        self.force_production = force_production;
+        self
+    }
+
+    /// Run the session, starting on `screen` with the main menu underneath it.
+    pub fn run(mut self, screen: Screen) -> Result<()> {
+        if screen != Screen::Main {
+            self.stack.push(screen);
+        }
+
+        while let Some(screen) = self.stack.last().cloned() {
+            let nav = match screen {
+                Screen::Main => self.main_menu()?,
+                Screen::Sites => self.sites_menu()?,
+                Screen::Site(id) => self.site_menu(&id)?,
+                Screen::Installs => self.installs_menu()?,
+                Screen::Install(id) => self.install_menu(&id)?,
+                Screen::Domains(id) => self.domains_menu(&id)?,
+                Screen::Accounts => self.accounts_menu()?,
+                Screen::Account(id) => self.account_menu(&id)?,
+                Screen::Users(id) => self.users_menu(&id)?
+            };
+
+            match nav {
+                Nav::Push(screen) => self.stack.push(screen),
+                Nav::Stay => {},
+                Nav::Back => {
+                    self.stack.pop();
+                },
+                Nav::Main => self.stack.truncate(1),
+                Nav::Quit => break
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Show `items` followed by the navigation entries that apply here.
+    fn menu(&self, prompt: &str, items: &[String]) -> Result<Choice> {
+        let mut nav = Vec::new();
+        if self.stack.len() > 1 {
+            nav.push((BACK, Nav::Back));
+        }
+        if self.stack.len() > 2 {
+            nav.push((MAIN_MENU, Nav::Main));
+        }
+        nav.push((CANCEL, Nav::Quit));
+
+        let mut labels = items.to_vec();
+        labels.extend(nav.iter().map(|(label, _)| label.to_string()));
+
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .items(&labels)
+            .default(0)
+            .interact_opt()?;
+
+        // Esc behaves like back.
+        let Some(i) = selection else { return Ok(Choice::Nav(Nav::Back)) };
+        if i < items.len() {
+            return Ok(Choice::Item(i));
+        }
+
+        Ok(Choice::Nav(nav.swap_remove(i - items.len()).1))
+    }
+
+    fn site(&mut self, id: &str) -> Result<Value> {
+        if let Some(site) = self.sites.get(id) {
+            return Ok(site.clone());
+        }
+        let site = self.api.get_site_by_id(id)?;
+        self.sites.insert(id.to_string(), site.clone());
+        Ok(site)
+    }
+
+    fn install(&mut self, id: &str) -> Result<Value> {
+        if let Some(install) = self.installs.get(id) {
+            return Ok(install.clone());
+        }
+        let install = self.api.get_install_by_id(id)?;
+        self.installs.insert(id.to_string(), install.clone());
+        Ok(install)
+    }
+
+    fn account(&mut self, id: &str) -> Result<Value> {
+        if let Some(account) = self.accounts.get(id) {
+            return Ok(account.clone());
+        }
+        let account = self.api.get_account_by_id(id)?;
+        self.accounts.insert(id.to_string(), account.clone());
+        Ok(account)
+    }
+
+    /// Forget a changed install along with the site listing it.
+    fn forget_install(&mut self, install: &Value) {
+        if let Some(id) = install["id"].as_str() {
+            self.installs.remove(id);
+        }
+        if let Some(site_id) = install["site"]["id"].as_str() {
+            self.sites.remove(site_id);
+        }
+        self.install_picker = None;
+    }
+
+    /// Confirm a delete through the guard. A refused one is explained and the menu stays open.
+    fn confirm_delete(&self, impact: &Impact) -> Result<bool> {
+        let guard = Guard { yes: false, force_production: self.force_production, headless: false };
+
+        match guard::confirm(impact, &guard) {
+            Ok(()) => Ok(true),
+            Err(err) if CliError::kind_of(&err) == ErrorKind::Usage => {
+                eprintln!("{}", err);
+                Ok(false)
+            },
+            Err(err) => Err(err)
+        }
+    }
+
+    /// Pick an account, unless the session is scoped to one. `None` means back.
+    fn pick_account(&mut self) -> Result<Option<String>> {
+        if let Some(id) = self.account {
+            return Ok(Some(id.to_string()));
+        }
+
+        let api = self.api;
+        let mut picker = self.account_picker
+            .take()
+            .unwrap_or_else(|| Picker::paged("Select an account", "name", move |page| api.get_accounts(Some(page))));
+        let account = picker.interact()?;
+        self.account_picker = Some(picker);
+
+        Ok(account.and_then(|a| a["id"].as_str().map(String::from)))
+    }
+
+    fn main_menu(&mut self) -> Result<Nav> {
+        let items = ["Sites", "Installs", "Accounts"].map(String::from);
+        Ok(match self.menu("Choose an option", &items)? {
+            Choice::Item(0) => Nav::Push(Screen::Sites),
+            Choice::Item(1) => Nav::Push(Screen::Installs),
+            Choice::Item(_) => Nav::Push(Screen::Accounts),
+            Choice::Nav(nav) => nav
+        })
+    }
+
+    fn sites_menu(&mut self) -> Result<Nav> {
+        let items = ["Browse sites", "Add site"].map(String::from);
+        match self.menu("Sites", &items)? {
+            Choice::Item(0) => {
+                let mut picker = match self.site_picker.take() {
+                    Some(picker) => picker,
+                    None => site_picker(self.api, &self.site_criteria, self.account, "Select a site")?
+                };
+                let site = picker.interact()?;
+                self.site_picker = Some(picker);
+
+                Ok(match site.as_ref().and_then(|s| s["id"].as_str()) {
+                    Some(id) => Nav::Push(Screen::Site(id.to_string())),
+                    None => Nav::Stay
+                })
+            },
+            Choice::Item(_) => {
+                println!("Follow the prompts to add a site.");
+                let site_name: String = Input::new()
+                    .with_prompt("Enter a site name")
+                    .interact()?;
+
+                let Some(account_id) = self.pick_account()? else { return Ok(Nav::Stay) };
+
+                let data = Site {
+                    name: site_name,
+                    account_id
+                };
+
+                let add_site = self.api.add_site(&data)?;
+                self.site_picker = None;
+
+                println!("Successfully added site:");
+                self.out.print(&add_site, Kind::Sites)?;
+
+                Ok(match add_site["id"].as_str() {
+                    Some(id) => Nav::Push(Screen::Site(id.to_string())),
+                    None => Nav::Stay
+                })
+            },
+            Choice::Nav(nav) => Ok(nav)
+        }
+    }
+
+    fn site_menu(&mut self, id: &str) -> Result<Nav> {
+        let site = self.site(id)?;
+        self.out.print(&site, Kind::Sites)?;
+
+        let installs = site["installs"].as_array().cloned().unwrap_or_default();
+        let mut items: Vec<String> = installs
+            .iter()
+            .map(|i| format!(
+                "Install: {} ({})",
+                i["name"].as_str().unwrap_or_default(),
+                i["environment"].as_str().unwrap_or_default()
+            ))
+            .collect();
+        items.push("Rename site".to_string());
+        items.push("Delete site".to_string());
+
+        let prompt = format!("Site: {}", site["name"].as_str().unwrap_or_default());
+        match self.menu(&prompt, &items)? {
+            Choice::Item(i) if i < installs.len() => {
+                let install_id = installs[i]["id"].as_str().ok_or_else(|| anyhow!("install has no ID"))?;
+                Ok(Nav::Push(Screen::Install(install_id.to_string())))
+            },
+            Choice::Item(i) if i == installs.len() => {
+                let site_name: String = Input::new()
+                    .with_prompt("Enter a site name")
+                    .allow_empty(true)
+                    .interact()?;
+
+                let data = SitePatch {
+                    name: (!site_name.is_empty()).then_some(site_name)
+                };
+                let what = format!("site '{}'", site["name"].as_str().unwrap_or(id));
+
+                if data.name.is_none() {
+                    println!("cancelling, no value provided.");
+                } else if diff::review(&what, &diff::changes(&site, &data, diff::SITE_FIELDS)?, false)? {
+                    let update_site = self.api.update_site(id, &data)?;
+                    self.sites.remove(id);
+                    self.site_picker = None;
+
+                    println!("Successfully update site:");
+                    self.out.print(&update_site, Kind::Sites)?;
+                }
+
+                // Answering no shows this site's options again.
+                Ok(Nav::Stay)
+            },
+            Choice::Item(_) => {
+                if self.confirm_delete(&Impact::site(self.api, id)?)? {
+                    self.api.delete_site(id)?;
+                    self.sites.remove(id);
+                    self.site_picker = None;
+
+                    println!("Site deleted!");
+                    return Ok(Nav::Back);
+                }
+
+                Ok(Nav::Stay)
+            },
+            Choice::Nav(nav) => Ok(nav)
+        }
+    }
+
+    fn installs_menu(&mut self) -> Result<Nav> {
+        let items = ["Browse installs", "Add install", "Bulk actions"].map(String::from);
+        match self.menu("Installs", &items)? {
+            Choice::Item(0) => {
+                let mut picker = match self.install_picker.take() {
+                    Some(picker) => picker,
+                    None => self.new_install_picker()?
+                };
+                let install = picker.interact()?;
+                self.install_picker = Some(picker);
+
+                Ok(match install.as_ref().and_then(|i| i["id"].as_str()) {
+                    Some(id) => Nav::Push(Screen::Install(id.to_string())),
+                    None => Nav::Stay
+                })
+            },
+            Choice::Item(1) => {
+                println!("Follow the prompts to add a install.");
+                let Some(account_id) = self.pick_account()? else { return Ok(Nav::Stay) };
+
+                let mut sites = site_picker(self.api, &Criteria::default(), Some(&account_id), "Select a site")?;
+                let Some(site) = sites.interact()? else { return Ok(Nav::Stay) };
+
+                let install: String = Input::new()
+                    .with_prompt("Enter an install name")
+                    .interact()?;
+
+                let environments = Environment::labels();
+                let environment = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Select an environment")
+                    .items(&environments)
+                    .default(0)
+                    .interact()?;
+
+                let data = Install {
+                    name: install,
+                    account_id: account_id.clone(),
+                    site_id: site["id"].as_str().ok_or_else(|| anyhow!("site has no ID"))?.to_string(),
+                    environment: environments[environment].to_string()
+                };
+
+                let add_install = self.api.add_install(&data)?;
+                self.forget_install(&add_install);
+
+                println!("Successfully added install:");
+                self.out.print(&add_install, Kind::Installs)?;
+
+                Ok(match add_install["id"].as_str() {
+                    Some(id) => Nav::Push(Screen::Install(id.to_string())),
+                    None => Nav::Stay
+                })
+            },
+            Choice::Item(_) => {
+                bulk::init(self.api, &self.install_criteria, self.account, self.force_production, self.out)?;
+
+                // Bulk actions can change or remove any install.
+                self.installs.clear();
+                self.sites.clear();
+                self.install_picker = None;
+                Ok(Nav::Stay)
+            },
+            Choice::Nav(nav) => Ok(nav)
+        }
+    }
+
+    /// Installs stream page by page, filtered as they arrive, unless they need sorting first.
+    fn new_install_picker(&self) -> Result<Picker<'a>> {
+        let api = self.api;
+        let account = self.account;
+        let criteria = self.install_criteria.clone();
+
+        if criteria.is_sorted() {
+            let installs = criteria.apply(api.get_all_installs(account)?);
+            if installs.is_empty() {
+                return Err(anyhow!("No installs match the given filters."));
+            }
+            return Ok(Picker::new("Select an install", "name", installs));
+        }
+
+        Ok(
+            Picker::paged("Select an install", "name", move |page| api.get_installs(Some(page), account))
+                .filter(move |install| criteria.matches(install))
+        )
+    }
+
+    fn install_menu(&mut self, id: &str) -> Result<Nav> {
+        let install = self.install(id)?;
+        self.out.print(&install, Kind::Installs)?;
+
+        let items = [
+            "Domains",
+            "Users",
+            "Change environment",
+            "Purge cache",
+            "Back up",
+            "Delete install"
+        ].map(String::from);
+
+        let prompt = format!("Install: {}", install["name"].as_str().unwrap_or_default());
+        match self.menu(&prompt, &items)? {
+            Choice::Item(0) => Ok(Nav::Push(Screen::Domains(id.to_string()))),
+            Choice::Item(1) => {
+                let account_id = install["account"]["id"]
+                    .as_str()
+                    .ok_or_else(|| anyhow!("Install {} has no account.", id))?;
+                Ok(Nav::Push(Screen::Users(account_id.to_string())))
+            },
+            Choice::Item(2) => {
+                let environments = Environment::labels();
+                let environment = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Select an environment")
+                    .items(&environments)
+                    .default(0)
+                    .interact()?;
+
+                let data = InstallPatch {
+                    environment: Some(environments[environment].to_string()),
+                    ..InstallPatch::default()
+                };
+
+                let what = format!("install '{}'", install["name"].as_str().unwrap_or(id));
+
+                if diff::review(&what, &diff::changes(&install, &data, diff::INSTALL_FIELDS)?, false)? {
+                    let update = self.api.update_install(id, &data)?;
+                    self.forget_install(&install);
+
+                    println!("Successfully updated install:");
+                    self.out.print(&update, Kind::Installs)?;
+                }
+
+                // Answering no shows this install's options again.
+                Ok(Nav::Stay)
+            },
+            Choice::Item(3) => {
+                let caches = CacheType::labels();
+                let kind = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Select a cache type")
+                    .items(&caches)
+                    .default(0)
+                    .interact()?;
+
+                self.api.purge_cache(id, caches[kind].to_string())?;
+                println!("Purged {} cache.", caches[kind]);
+                Ok(Nav::Stay)
+            },
+            Choice::Item(4) => {
+                let description: String = Input::new()
+                    .with_prompt("Enter a backup description")
+                    .interact()?;
+
+                let data = Backup {
+                    description,
+                    notification_emails: Vec::new()
+                };
+
+                let backup = self.api.backup(id, &data)?;
+                println!("Backup requested:");
+                self.out.print(&backup, Kind::Other)?;
+                Ok(Nav::Stay)
+            },
+            Choice::Item(_) => {
+                if self.confirm_delete(&Impact::install(self.api, id)?)? {
+                    self.api.delete_install(id)?;
+                    self.forget_install(&install);
+
+                    println!("Install deleted!");
+                    return Ok(Nav::Back);
+                }
+
+                Ok(Nav::Stay)
+            },
+            Choice::Nav(nav) => Ok(nav)
+        }
+    }
+
+    fn domains_menu(&mut self, install_id: &str) -> Result<Nav> {
+        if !self.domains.contains_key(install_id) {
+            let domains = self.api.get_all_domains(install_id)?;
+            self.domains.insert(install_id.to_string(), domains);
+        }
+        let domains = &self.domains[install_id];
+
+        let items: Vec<String> = domains
+            .iter()
+            .map(|d| {
+                let name = d["name"].as_str().unwrap_or_default();
+                if d["primary"].as_bool().unwrap_or(false) {
+                    format!("{} (primary)", name)
+                } else {
+                    name.to_string()
+                }
+            })
+            .collect();
+
+        match self.menu("Domains", &items)? {
+            Choice::Item(i) => {
+                self.out.print(&self.domains[install_id][i], Kind::Domains)?;
+                Ok(Nav::Stay)
+            },
+            Choice::Nav(nav) => Ok(nav)
+        }
+    }
+
+    fn accounts_menu(&mut self) -> Result<Nav> {
+        let items = ["Browse accounts".to_string()];
+        match self.menu("Accounts", &items)? {
+            Choice::Item(_) => {
+                // Scoping doesn't apply here, browsing accounts is the point.
+                let api = self.api;
+                let mut picker = self.account_picker
+                    .take()
+                    .unwrap_or_else(|| Picker::paged("Select an account", "name", move |page| api.get_accounts(Some(page))));
+                let account = picker.interact()?;
+                self.account_picker = Some(picker);
+
+                Ok(match account.as_ref().and_then(|a| a["id"].as_str()) {
+                    Some(id) => Nav::Push(Screen::Account(id.to_string())),
+                    None => Nav::Stay
+                })
+            },
+            Choice::Nav(nav) => Ok(nav)
+        }
+    }
+
+    fn account_menu(&mut self, id: &str) -> Result<Nav> {
+        let account = self.account(id)?;
+        self.out.print(&account, Kind::Accounts)?;
+
+        let items = ["Sites", "Users"].map(String::from);
+        let prompt = format!("Account: {}", account["name"].as_str().unwrap_or_default());
+        match self.menu(&prompt, &items)? {
+            Choice::Item(0) => {
+                let mut sites = site_picker(self.api, &self.site_criteria, Some(id), "Select a site")?;
+                Ok(match sites.interact()?.as_ref().and_then(|s| s["id"].as_str()) {
+                    Some(site_id) => Nav::Push(Screen::Site(site_id.to_string())),
+                    None => Nav::Stay
+                })
+            },
+            Choice::Item(_) => Ok(Nav::Push(Screen::Users(id.to_string()))),
+            Choice::Nav(nav) => Ok(nav)
+        }
+    }
+
+    fn users_menu(&mut self, account_id: &str) -> Result<Nav> {
+        if !self.users.contains_key(account_id) {
+            let users = self.api.get_all_account_users(account_id)?;
+            self.users.insert(account_id.to_string(), users);
+        }
+        let users = &self.users[account_id];
+
+        let items: Vec<String> = users
+            .iter()
+            .map(|u| format!(
+                "{} ({})",
+                u["email"].as_str().unwrap_or_default(),
+                u["roles"].as_str().unwrap_or_default()
+            ))
+            .collect();
+
+        match self.menu("Users", &items)? {
+            Choice::Item(i) => {
+                self.out.print(&self.users[account_id][i], Kind::Users)?;
+                Ok(Nav::Stay)
+            },
+            Choice::Nav(nav) => Ok(nav)
+        }
+    }
+}
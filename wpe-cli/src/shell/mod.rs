@@ -0,0 +1,484 @@
+use clap::{Arg, Command, Parser};
+use rustyline::{
+    Editor,
+    Helper,
+    completion::{Completer, Pair},
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    history::DefaultHistory,
+    validate::Validator
+};
+use home_config::HomeConfig;
+use anyhow::{anyhow, Result};
+use wpe::*;
+use crate::cli::{Cli, Commands};
+use crate::complete::{self, KINDS, expected, resource_of, walk};
+use crate::output::{self, Format};
+
+const BUILTINS: [&str; 7] = ["use", "unuse", "context", "refresh", "help", "exit", "quit"];
+
+/// Options that say what to change rather than which resource to act on, keyed by
+/// subcommand and argument. The context never fills these, e.g. `installs update
+/// acmeprod --environment staging` must not move the install to the current site.
+const NOT_FILLED: [(&str, &str); 3] = [("update", "site"), ("add", "installs"), ("update", "installs")];
+
+/// The account, site and install that commands fall back to when an ID is left out.
+#[derive(Default)]
+struct Context {
+    account: Option<IndexEntry>,
+    site: Option<IndexEntry>,
+    install: Option<IndexEntry>
+}
+
+impl Context {
+    fn get(&self, kind: Resource) -> Option<&IndexEntry> {
+        match kind {
+            Resource::Account => self.account.as_ref(),
+            Resource::Site => self.site.as_ref(),
+            Resource::Install => self.install.as_ref()
+        }
+    }
+
+    fn prompt(&self) -> String {
+        let names: Vec<&str> = KINDS
+            .iter()
+            .filter_map(|k| self.get(*k).map(|e| e.name.as_str()))
+            .collect();
+
+        if names.is_empty() {
+            "wpe> ".to_string()
+        } else {
+            format!("wpe [{}]> ", names.join(" › "))
+        }
+    }
+}
+
+fn parse_kind(word: &str) -> Result<Resource> {
+    KINDS
+        .into_iter()
+        .find(|k| k.label() == word.to_lowercase())
+        .ok_or_else(|| anyhow!("Unknown resource '{}', expected account, site or install.", word))
+}
+
+/// Fill the resources the line left out from the context, so `domains list`
+/// acts on the current install and `installs add` goes on the current site.
+fn fill(root: &Command, words: Vec<String>, context: &Context) -> Vec<String> {
+    let (cmd, given) = walk(root, &words);
+    let mut words = fill_positionals(cmd, &given, words, context);
+
+    // Flags like `--install` are appended, flags are accepted anywhere after the verb.
+    // A file gives its own resources, so nothing that conflicts with it is filled.
+    let file = words.iter().any(|w| w == "-f" || w == "--file" || w.starts_with("--file="));
+    for arg in cmd.get_opts().filter(|a| !NOT_FILLED.contains(&(cmd.get_name(), a.get_id().as_str()))) {
+        let (Some(long), Some(entry)) = (arg.get_long(), resource_of(arg).and_then(|k| context.get(k))) else {
+            continue;
+        };
+        if file && cmd.get_arg_conflicts_with(arg).iter().any(|a| a.get_id() == "file") {
+            continue;
+        }
+        let flag = format!("--{}", long);
+        if !words.iter().any(|w| *w == flag || w.starts_with(&format!("{}=", flag))) {
+            words.extend([flag, entry.id.clone()]);
+        }
+    }
+
+    words
+}
+
+/// Fill required positionals, `given` holds the indexes of the ones on the line.
+/// A resource that is only required without `--file` is filled too.
+fn fill_positionals(cmd: &Command, given: &[usize], words: Vec<String>, context: &Context) -> Vec<String> {
+    let args: Vec<&Arg> = cmd.get_positionals().collect();
+    let fillable = |a: &Arg| a.is_required_set() || resource_of(a).is_some();
+    let required = args.iter().filter(|a| fillable(a)).count();
+    let mut missing = required.saturating_sub(given.len());
+    if missing == 0 {
+        return words;
+    }
+
+    let mut values = given.iter().map(|i| words[*i].clone());
+    let mut positionals = Vec::new();
+    for arg in &args {
+        match resource_of(arg).and_then(|k| context.get(k)) {
+            Some(entry) if missing > 0 && fillable(arg) => {
+                positionals.push(entry.id.clone());
+                missing -= 1;
+            },
+            _ => match values.next() {
+                Some(value) => positionals.push(value),
+                None => break
+            }
+        }
+    }
+    positionals.extend(values);
+
+    // Flags keep their place, the positional values move to the end in their new order.
+    let mut filled: Vec<String> = words
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !given.contains(i))
+        .map(|(_, w)| w.clone())
+        .collect();
+    filled.extend(positionals);
+    filled
+}
+
+/// Split the text before the cursor into finished words and the word being typed,
+/// along with where that word starts.
+fn split_words(line: &str) -> (usize, Vec<String>, String) {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut start = 0;
+    let mut quote: Option<char> = None;
+
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => current.push(c),
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c.is_whitespace() => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+                start = i + c.len_utf8();
+            },
+            None => current.push(c)
+        }
+    }
+
+    (start, words, current)
+}
+
+/// Tab completion from the command tree and the cached resource index.
+struct ShellHelper {
+    cli: Command,
+    index: ResourceIndex
+}
+
+impl ShellHelper {
+    fn names(&self, kind: Resource) -> Vec<String> {
+        self.index.entries(kind).iter().map(|e| e.name.clone()).collect()
+    }
+
+    fn candidates(&self, words: &[String], partial: &str) -> Vec<String> {
+        let kinds = || KINDS.iter().map(|k| k.label().to_string()).collect();
+
+        match words.first().map(String::as_str) {
+            None => BUILTINS
+                .iter()
+                .map(|b| b.to_string())
+                .chain(
+                    self.cli
+                        .get_subcommands()
+                        .filter(|s| !s.is_hide_set() && s.get_name() != "help")
+                        .map(|s| s.get_name().to_string())
+                )
+                .collect(),
+            Some("use") => match words.len() {
+                1 => kinds(),
+                2 => parse_kind(&words[1]).map(|k| self.names(k)).unwrap_or_default(),
+                _ => Vec::new()
+            },
+            Some("unuse") if words.len() == 1 => kinds(),
+            Some(_) if words.last().is_some_and(|w| w.starts_with("--")) => {
+                expected(&self.cli, words).map(|k| self.names(k)).unwrap_or_default()
+            },
+            Some(_) => {
+                let (cmd, given) = walk(&self.cli, words);
+                if partial.starts_with('-') {
+                    return cmd
+                        .get_arguments()
+                        .filter_map(|a| a.get_long().map(|l| format!("--{}", l)))
+                        .collect();
+                }
+
+                let mut candidates: Vec<String> = if given.is_empty() {
+                    cmd.get_subcommands()
+                        .filter(|s| !s.is_hide_set() && s.get_name() != "help")
+                        .map(|s| s.get_name().to_string())
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                if let Some(kind) = cmd.get_positionals().nth(given.len()).and_then(resource_of) {
+                    candidates.extend(self.names(kind));
+                }
+                candidates
+            }
+        }
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &rustyline::Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let (start, words, partial) = split_words(&line[..pos]);
+        let prefix = partial.to_lowercase();
+
+        let mut pairs: Vec<Pair> = self
+            .candidates(&words, &partial)
+            .into_iter()
+            .filter(|c| c.to_lowercase().starts_with(&prefix))
+            .map(|c| Pair {
+                replacement: if c.contains(char::is_whitespace) {
+                    format!("\"{}\" ", c)
+                } else {
+                    format!("{} ", c)
+                },
+                display: c
+            })
+            .collect();
+        pairs.dedup_by(|a, b| a.replacement == b.replacement);
+
+        Ok((start, pairs))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+/// Look up the name of a resource for display, falling back to its ID.
+fn entry(index: &ResourceIndex, kind: Resource, id: &str) -> IndexEntry {
+    index
+        .matches(kind, id)
+        .first()
+        .map(|e| (*e).clone())
+        .unwrap_or_else(|| IndexEntry { id: id.to_string(), name: id.to_string() })
+}
+
+/// Set part of the context. Narrower resources fill in what they belong to.
+fn use_resource(api: &API, context: &mut Context, kind: Resource, input: &str) -> Result<()> {
+    let id = api.resolve(kind, input)?;
+    let index = ResourceIndex::load()?;
+
+    match kind {
+        Resource::Account => {
+            let account = api.get_account_by_id(&id)?;
+            *context = Context {
+                account: Some(IndexEntry { id, name: account["name"].as_str().unwrap_or_default().to_string() }),
+                ..Context::default()
+            };
+        },
+        Resource::Site => {
+            let site = api.get_site_by_id(&id)?;
+            *context = Context {
+                account: site["account"]["id"].as_str().map(|a| entry(&index, Resource::Account, a)),
+                site: Some(IndexEntry { id, name: site["name"].as_str().unwrap_or_default().to_string() }),
+                install: None
+            };
+        },
+        Resource::Install => {
+            let install = api.get_install_by_id(&id)?;
+            *context = Context {
+                account: install["account"]["id"].as_str().map(|a| entry(&index, Resource::Account, a)),
+                site: install["site"]["id"].as_str().map(|s| entry(&index, Resource::Site, s)),
+                install: Some(IndexEntry { id, name: install["name"].as_str().unwrap_or_default().to_string() })
+            };
+        }
+    }
+
+    Ok(())
+}
+
+fn print_help() {
+    println!("Type any wpe command without the leading `wpe`, e.g. `installs list` or `sites list --filter name~acme`.");
+    println!("Commands run in headless mode and leave out IDs that the context can fill in.");
+    println!();
+    println!("  use <account|site|install> <name or ID>  Set the current context");
+    println!("  unuse [account|site|install]             Clear the context, or part of it");
+    println!("  context                                  Show the current context");
+    println!("  refresh                                  Rebuild the cached names used for completion");
+    println!("  help                                     Show this help, `help <command>` for a command");
+    println!("  exit                                     Leave the shell");
+}
+
+/// Run one line. Returns `false` once the shell should exit.
+fn execute(api: &API, context: &mut Context, words: Vec<String>) -> Result<bool> {
+    match words[0].as_str() {
+        "exit" | "quit" => return Ok(false),
+        "help" if words.len() == 1 => print_help(),
+        "context" => {
+            for kind in KINDS {
+                match context.get(kind) {
+                    Some(e) => println!("{}: {} ({})", kind.label(), e.name, e.id),
+                    None => println!("{}: -", kind.label())
+                }
+            }
+        },
+        "use" => {
+            let [_, kind, input] = words.as_slice() else {
+                return Err(anyhow!("Usage: use <account|site|install> <name or ID>"));
+            };
+            use_resource(api, context, parse_kind(kind)?, input)?;
+        },
+        "unuse" => match words.get(1) {
+            None => *context = Context::default(),
+            Some(kind) => match parse_kind(kind)? {
+                Resource::Account => *context = Context::default(),
+                Resource::Site => {
+                    context.site = None;
+                    context.install = None;
+                },
+                Resource::Install => context.install = None
+            }
+        },
+        "refresh" => {
+            let index = api.refresh_index()?;
+            println!(
+                "Indexed {} accounts, {} sites and {} installs.",
+                index.accounts.len(),
+                index.sites.len(),
+                index.installs.len()
+            );
+        },
+        _ => {
+            let root = complete::command();
+            let mut words = fill(&root, words, context);
+
+            // Commands follow the current account unless one is given.
+            if let Some(account) = context.account.as_ref() {
+                if !words.iter().any(|w| w == "--account" || w.starts_with("--account=")) {
+                    words.splice(0..0, ["--account".to_string(), account.id.clone()]);
+                }
+            }
+
+            let mut argv = vec!["wpe".to_string()];
+            if !words.iter().any(|w| w == "-H" || w == "--headless") {
+                argv.push("-H".to_string());
+            }
+            argv.extend(words);
+
+            let cli = match Cli::try_parse_from(argv) {
+                Ok(cli) => cli,
+                Err(e) => {
+                    // Covers `help <command>` and `--help` as well as real usage errors.
+                    e.print()?;
+                    return Ok(true);
+                }
+            };
+
+            if let Commands::Shell = cli.command {
+                println!("Already in the shell.");
+            } else {
+                // `--dry-run` on a line holds back that line's requests, the shell's own flag all of them.
+                let api = api.clone().dry_run(api.is_dry_run() || cli.global.dry_run);
+                // Errors are reported in the format the line asked for, and the shell goes on.
+                if let Err(e) = crate::run(&cli, &api) {
+                    output::print_error(&e, cli.global.output);
+                }
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+/// Provides logic for the shell command.
+///
+/// # Arguments
+///
+/// * `api` - &API
+/// * `format` - Option<Format>, how errors of the shell itself are reported
+pub fn init(api: &API, format: Option<Format>) -> Result<()> {
+    // Completion needs names, build the index if it has never been or is out of date.
+    let mut index = ResourceIndex::load()?;
+    if index.is_stale() {
+        index = api.refresh_index()?;
+    }
+
+    let mut editor: Editor<ShellHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(ShellHelper { cli: complete::command(), index }));
+
+    let history = HomeConfig::with_config_dir("wpe", "history");
+    // There is no history the first time round.
+    let _ = editor.load_history(history.path());
+
+    println!("wpe shell, type `help` for commands and `exit` to leave.");
+    let mut context = Context::default();
+
+    loop {
+        let line = match editor.readline(&context.prompt()) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into())
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line.as_str())?;
+
+        let Some(words) = shlex::split(&line) else {
+            output::print_error(&CliError::new(ErrorKind::Usage, "unbalanced quotes").into(), format);
+            continue;
+        };
+
+        match execute(api, &mut context, words) {
+            Ok(true) => {},
+            Ok(false) => break,
+            Err(e) => {
+                output::print_error(&e, format);
+            }
+        }
+
+        // Resolving names may have rebuilt the index on disk.
+        if let (Some(helper), Ok(index)) = (editor.helper_mut(), ResourceIndex::load()) {
+            helper.index = index;
+        }
+    }
+
+    editor
+        .save_history(history.path())
+        .map_err(|e| anyhow!("Unable to save shell history: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> Context {
+        let entry = |id: &str, name: &str| Some(IndexEntry { id: id.to_string(), name: name.to_string() });
+        Context { account: entry("a1", "acme"), site: entry("s1", "Acme Corp"), install: entry("i1", "acmeprod") }
+    }
+
+    fn filled(line: &str) -> String {
+        let words = line.split_whitespace().map(String::from).collect();
+        fill(&complete::command(), words, &context()).join(" ")
+    }
+
+    #[test]
+    fn fills_resource_flags_that_are_only_required_without_a_file() {
+        let line = filled("installs add --name x --environment staging");
+        assert!(line.starts_with("installs add --name x --environment staging"));
+        assert!(line.contains("--site s1"));
+        assert!(Cli::try_parse_from(["wpe".to_string()].into_iter().chain(line.split(' ').map(String::from))).is_ok());
+    }
+
+    #[test]
+    fn keeps_what_the_line_gives() {
+        assert!(filled("installs add --name x --environment staging --site s2").contains("--site s2"));
+        assert!(!filled("installs add --name x --environment staging --site s2").contains("s1"));
+        assert!(!filled("installs add -f installs.yaml").contains("--site"));
+    }
+
+    #[test]
+    fn fills_positionals_and_required_flags() {
+        assert_eq!(filled("installs get"), "installs get i1 --account a1");
+        assert!(filled("domains list").contains("--install i1"));
+    }
+
+    #[test]
+    fn never_fills_what_a_command_changes() {
+        assert!(!filled("installs update acmestg --environment staging").contains("--site"));
+        assert!(!filled("users add --email a@b.c --first-name A --last-name B --roles partial").contains("--install"));
+    }
+}
@@ -1,177 +1,135 @@
-use dialoguer::{
-    Select,
-    Input,
-    Confirm,
-    theme::ColorfulTheme
-};
-use clap::ArgMatches;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use wpe::*;
+use serde_json::Value;
+use crate::output::{Output, Kind};
+use crate::filter::Criteria;
+use crate::cli::{SitesArgs, SiteCommand};
+use crate::session::{Session, Screen};
+use crate::accounts;
+use crate::guard::{self, Guard, Impact};
+use crate::diff;
+use crate::input::{Bodies, Update};
+
+/// Site picker for the interactive flows. Streams pages from the API, filtering
+/// each one as it arrives. Sorting needs every site up front.
+pub fn site_picker<'a>(
+    api: &'a API,
+    criteria: &Criteria,
+    account: Option<&'a str>,
+    prompt: &str
+) -> Result<Picker<'a>> {
+    if !criteria.is_sorted() {
+        let criteria = criteria.clone();
+        return Ok(
+            Picker::paged(prompt, "name", move |page| api.get_sites(Some(page), account))
+                .filter(move |site| criteria.matches(site))
+        );
+    }
+
+    let sites = criteria.apply(api.get_all_sites(account)?);
+    if sites.is_empty() {
+        return Err(anyhow!("No sites match the given filters."));
+    }
+
+    Ok(Picker::new(prompt, "name", sites))
+}
+
+/// Update a site once its diff is reviewed. Returns null when nothing was sent.
+fn update(api: &API, id: &str, data: &SitePatch, headless: bool) -> Result<Value> {
+    let current = api.get_site_by_id(id)?;
+    let what = format!("site '{}'", current["name"].as_str().unwrap_or(id));
+    if !diff::review(&what, &diff::changes(&current, data, diff::SITE_FIELDS)?, headless)? {
+        return Ok(Value::Null);
+    }
+
+    api.update_site(id, data)
+}
 
 /// Provides logic for the sites command.
 ///
 /// # Arguments
 ///
-/// * `sub_n` - &ArgMatches
-/// * `command` - API
-/// * `headless` - Option<&bool>
-pub fn init(sub_n: &ArgMatches, api: API, headless: Option<&bool>) -> Result<()> {
-    let page = sub_n.get_one::<String>("PAGE");
-    let page_num: u8;
-
-    match page {
-        Some(x) => {
-            page_num = x.parse::<u8>().unwrap();
+/// * `args` - &SitesArgs
+/// * `api` - &API
+/// * `headless` - bool
+/// * `out` - &Output
+/// * `account` - Option<&str>
+pub fn init(
+    args: &SitesArgs,
+    api: &API,
+    headless: bool,
+    out: &Output,
+    account: Option<&str>
+) -> Result<()> {
+    let criteria = &args.criteria;
+
+    match &args.command {
+        Some(SiteCommand::Get { site }) => {
+            let id = api.resolve(Resource::Site, site)?;
+            let site = api.get_site_by_id(&id)?;
+            out.print(&site, Kind::Sites)?;
         },
-        None => {
-            page_num = 0;
-        }
-    }
-
-    // Fetch sites and display results. Will also show paginated results.
-    let next = api.get_sites(Some(page_num))?;
-    let results = next["results"].as_array().unwrap();
-
-    // Check for headless mode.
-    if let Some(true) = headless {
-        match sub_n.subcommand() {
-            Some(("list", sub)) => {
-                if let Some(id) = sub.get_one::<String>("ID") {
-                    let site = api.get_site_by_id(&id.as_str())?;
-                    println!("{}", serde_json::to_string_pretty(&site)?);
-
-                } else {
-                    println!("{}", serde_json::to_string_pretty(results)?);
+        Some(SiteCommand::Add { name, body }) => {
+            let mut bodies = Bodies::from(&body.file, || Ok(Site {
+                name: name.clone().unwrap_or_default(),
+                account_id: String::new()
+            }))?;
+            if bodies.items.iter().any(|s| s.account_id.is_empty()) {
+                let account_id = accounts::require(api, account, headless, "add a site")?;
+                for data in bodies.items.iter_mut().filter(|s| s.account_id.is_empty()) {
+                    data.account_id = account_id.clone();
                 }
-            },
-            Some(("add", sub)) => {
-                let name = sub.get_one::<String>("NAME").unwrap();
-                let id = sub.get_one::<String>("ID").unwrap();
-
-                let data = Site {
-                    name: name.to_string(),
-                    account_id: id.to_string()
-                };
-
-                let add_site = api.add_site(&data)?;
-
-                println!("{}", serde_json::to_string_pretty(&add_site)?);
-            },
-            Some(("update", sub)) => {
-                let name = sub.get_one::<String>("NAME");
-                let id = sub.get_one::<String>("ID").unwrap();
-
-                let data = SitePatch {
-                    name: name.cloned()
-                };
-
-                let update_site = api.update_site(id.as_str(), &data)?;
-
-                println!("{}", serde_json::to_string_pretty(&update_site)?);
-            },
-            Some(("delete", sub)) => {
-                let id = sub.get_one::<String>("ID").unwrap();
-
-                api.delete_site(&id.as_str())?;
-            },
-            _ => {
-                println!("{}", serde_json::to_string_pretty(results)?);
             }
-        }
-    } else {
-        // Handle logic for when headless mode is not enabled
-        let options: [&str; 4] = ["List All", "Add site", "Update Site", "Delete Site"];
-        let selection = Select::with_theme(&ColorfulTheme::default())
-            .with_prompt("Choose an option")
-            .items(&options)
-            .interact()?;
-
-        match selection {
-            0 => {
-                // Handle logic for listing sites.
-                let site_selections = get_selections!(results, "Select a site to view", "name");
-
-                let item = &results[site_selections]["id"];
-                let site = api.get_site_by_id(&item.as_str().unwrap())?;
-
-                println!("Selection: {}", serde_json::to_string_pretty(&site)?);
-            },
-            1 => {
-                // Logic for adding a site.
-                println!("Follow the prompts to add a site.");
-                let site_name = Input::new()
-                    .with_prompt("Enter a site name")
-                    .interact()?;
-
-                let accounts_results = api.get_accounts(Some(0))?;
-                let accounts = accounts_results["results"].as_array().unwrap();
-
-                let account = get_selections!(accounts, "Select an account", "name");
-
-                let data = Site {
-                    name: site_name,
-                    account_id: accounts[account]["id"].as_str().unwrap().to_string()
-                };
-
-                let add_site = api.add_site(&data)?;
-
-                println!(
-                    "Successfully added site: {}",
-                    serde_json::to_string_pretty(&add_site)?
-                );
-            },
-            2 => {
-                // Logic for updating a site.
-                let site_selections = get_selections!(results, "Select a site to update", "name");
-
-                let site = &results[site_selections]["id"].as_str().unwrap().to_string();
-                let site_name: String = Input::new()
-                    .with_prompt("Enter a site name")
-                    .allow_empty(true)
-                    .interact()?;
-
-                if site_name.is_empty() {
-                    println!("cancelling, no value provided.");
-                } else {
-                    if Confirm::new().with_prompt("Does this data look right?").interact()? {
-
-                        // Need to do something better to handle optional values.
-                        let data = SitePatch {
-                            name: Some(site_name)
-                        };
 
-                        let update_site = api.update_site(site.as_str(), &data)?;
-                        println!(
-                            "Successfully update site: {}",
-                            serde_json::to_string_pretty(&update_site)?
-                            );
+            let add_site = bodies.items.iter().map(|data| api.add_site(data)).collect::<Result<Vec<_>>>()?;
 
-                    } else {
-                        // Recursively call init to show prompts again.
-                        init(sub_n, api, headless)?;
-                    }
+            out.print(&bodies.output(add_site), Kind::Sites)?;
+        },
+        Some(SiteCommand::Update { site, name, body }) => {
+            let bodies = Bodies::from(&body.file, || {
+                if name.is_none() {
+                    return Err(CliError::new(ErrorKind::Usage, "Nothing to update, pass --name.").into());
                 }
+                Ok(Update::new(SitePatch { name: name.clone() }))
+            })?;
 
-            },
-            3 => {
-                // Logic for deleting a site.
-                let site_selections = get_selections!(results, "Select a site to update", "name");
-
-                let site = &results[site_selections]["id"].as_str().unwrap().to_string();
-
-                if Confirm::new().with_prompt("Are you sure?").interact()? {
+            let mut update_site = Vec::new();
+            for body in &bodies.items {
+                let id = api.resolve(Resource::Site, body.target(site.as_deref())?)?;
+                update_site.push(update(api, &id, &body.patch, headless)?);
+            }
 
-                        api.delete_site(site)?;
-                        println!("Site deleted!");
+            out.print(&bodies.output(update_site), Kind::Sites)?;
+        },
+        Some(SiteCommand::Delete { site, confirm }) => {
+            let id = api.resolve(Resource::Site, site)?;
+            let guard = Guard { yes: confirm.yes, force_production: confirm.force_production, headless };
+            guard::confirm(&Impact::site(api, &id)?, &guard)?;
 
-                    } else {
-                        println!("Cancelling.");
-                    }
-            },
-            _ => println!("An error occured with your selection")
+            api.delete_site(&id)?;
+        },
+        Some(SiteCommand::List) => list(api, args.page, criteria, out, account)?,
+        None if headless => list(api, args.page, criteria, out, account)?,
+        None => {
+            // The session keeps a stack of screens, so back and main menu work from anywhere.
+            Session::new(api, out, account)
+                .site_criteria(criteria.clone())
+                .force_production(args.force_production)
+                .run(Screen::Sites)?;
         }
-
     }
+
     Ok(())
 }
 
+/// Print a page of sites. Filters and sorting need every site, not just the requested page.
+fn list(api: &API, page: u32, criteria: &Criteria, out: &Output, account: Option<&str>) -> Result<()> {
+    let results: Vec<Value> = if criteria.is_empty() {
+        let next = api.get_sites(Some(page), account)?;
+        next["results"].as_array().cloned().unwrap_or_default()
+    } else {
+        criteria.apply(api.get_all_sites(account)?)
+    };
+
+    out.print(&results, Kind::Sites)
+}
@@ -0,0 +1,42 @@
+use anyhow::Result;
+use wpe::*;
+use crate::output::{Output, Kind};
+use crate::cli::{SshKeysArgs, SshKeyCommand};
+use crate::guard::{self, Guard, Impact};
+use crate::input::Bodies;
+
+/// Provides logic for the ssh-keys command.
+///
+/// # Arguments
+///
+/// * `args` - &SshKeysArgs
+/// * `api` - &API
+/// * `headless` - bool
+/// * `out` - &Output
+pub fn init(args: &SshKeysArgs, api: &API, headless: bool, out: &Output) -> Result<()> {
+    match &args.command {
+        SshKeyCommand::List { page } => {
+            let keys = api.get_ssh_keys(Some(*page))?;
+            out.print(&keys["results"], Kind::SshKeys)?;
+        },
+        SshKeyCommand::Add { public_key, body } => {
+            let bodies = Bodies::from(&body.file, || Ok(SSHKey {
+                public_key: public_key.clone().unwrap_or_default()
+            }))?;
+
+            let add_key = bodies.items
+                .iter()
+                .map(|data| api.add_ssh_key(&SSHKey { public_key: data.public_key.trim().to_string() }))
+                .collect::<Result<Vec<_>>>()?;
+
+            out.print(&bodies.output(add_key), Kind::SshKeys)?;
+        },
+        SshKeyCommand::Delete { key, yes } => {
+            let guard = Guard { yes: *yes, force_production: false, headless };
+            guard::confirm(&Impact::new("SSH key", key), &guard)?;
+            api.delete_ssh_key(key)?;
+        }
+    }
+
+    Ok(())
+}
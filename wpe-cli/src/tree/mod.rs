@@ -0,0 +1,110 @@
+use serde_json::{json, Value};
+use anyhow::Result;
+use wpe::*;
+use crate::output::{self, Format, Kind, Output};
+
+/// The key holding each level's children: accounts hold sites, sites hold installs, installs hold domains.
+const CHILDREN: [&str; 3] = ["sites", "installs", "domains"];
+
+/// Keep the items whose reference to their parent matches, e.g. the sites of an account.
+fn children_of<'v>(items: &'v [Value], parent: &'v str, id: &'v Value) -> impl Iterator<Item = &'v Value> {
+    items.iter().filter(move |i| &i[parent]["id"] == id)
+}
+
+/// Build the nested account → site → install → domain structure down to `depth` levels.
+fn build(api: &API, account: Option<&str>, depth: u8) -> Result<Vec<Value>> {
+    let accounts: Vec<Value> = api
+        .get_all_accounts()?
+        .into_iter()
+        .filter(|a| account.is_none_or(|id| a["id"] == id))
+        .collect();
+
+    let sites = if depth > 1 { api.get_all_sites(account)? } else { Vec::new() };
+    let installs = if depth > 2 { api.get_all_installs(account)? } else { Vec::new() };
+
+    let mut tree = Vec::new();
+    for account in &accounts {
+        let mut account_node = json!({ "id": account["id"], "name": account["name"] });
+
+        if depth > 1 {
+            let mut site_nodes = Vec::new();
+            for site in children_of(&sites, "account", &account["id"]) {
+                let mut site_node = json!({ "id": site["id"], "name": site["name"] });
+
+                if depth > 2 {
+                    let mut install_nodes = Vec::new();
+                    for install in children_of(&installs, "site", &site["id"]) {
+                        let mut install_node = json!({
+                            "id": install["id"],
+                            "name": install["name"],
+                            "environment": install["environment"]
+                        });
+
+                        if depth > 3 {
+                            let domains: Vec<Value> = api
+                                .get_all_domains(install["id"].as_str().unwrap_or_default())?
+                                .iter()
+                                .map(|d| json!({ "id": d["id"], "name": d["name"], "primary": d["primary"] }))
+                                .collect();
+                            install_node["domains"] = Value::Array(domains);
+                        }
+                        install_nodes.push(install_node);
+                    }
+                    site_node["installs"] = Value::Array(install_nodes);
+                }
+                site_nodes.push(site_node);
+            }
+            account_node["sites"] = Value::Array(site_nodes);
+        }
+        tree.push(account_node);
+    }
+
+    Ok(tree)
+}
+
+fn label(level: usize, node: &Value) -> String {
+    let name = node["name"].as_str().unwrap_or_default();
+    match level {
+        2 => format!("{} ({})", name, node["environment"].as_str().unwrap_or_default()),
+        3 if node["primary"].as_bool().unwrap_or(false) => format!("{} (primary)", name),
+        _ => name.to_string()
+    }
+}
+
+/// Draw the children of a node with box-drawing connectors.
+fn render(node: &Value, level: usize, prefix: &str, text: &mut String) {
+    let Some(children) = CHILDREN.get(level).and_then(|key| node[key].as_array()) else { return };
+
+    for (i, child) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        let (branch, indent) = if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+
+        text.push_str(&format!("{}{}{}\n", prefix, branch, label(level + 1, child)));
+        render(child, level + 1, &format!("{}{}", prefix, indent), text);
+    }
+}
+
+/// Provides logic for the tree command.
+///
+/// # Arguments
+///
+/// * `depth` - u8
+/// * `api` - &API
+/// * `out` - &Output
+/// * `account` - Option<&str>
+pub fn init(depth: u8, api: &API, out: &Output, account: Option<&str>) -> Result<()> {
+    let tree = build(api, account, depth)?;
+
+    // The drawn tree only stands in for a table, other formats get the nested data.
+    if out.format != Format::Table || out.query.is_some() || out.fields.is_some() {
+        return out.print(&tree, Kind::Other);
+    }
+
+    let mut text = String::new();
+    for account in &tree {
+        text.push_str(&format!("{} ({})\n", label(0, account), account["id"].as_str().unwrap_or_default()));
+        render(account, 0, "", &mut text);
+    }
+
+    output::write_stdout(&text)
+}
@@ -0,0 +1,551 @@
+use std::io::{IsTerminal, Write};
+use std::process::Stdio;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ratatui::{
+    DefaultTerminal,
+    Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, List, ListItem, ListState, Paragraph, Wrap}
+};
+use serde_json::Value;
+use anyhow::Result;
+use wpe::*;
+
+const HELP: &str = "tab switch · ↑↓ move · p purge · b backup · o open · s copy ssh · r refresh · q quit";
+
+/// Work handed to the background thread so drawing never waits on the API.
+enum Request {
+    Accounts,
+    Sites(String),
+    Install(String),
+    PurgeCache(String, String),
+    Backup(String, String)
+}
+
+/// What the background thread sends back.
+enum Reply {
+    Accounts(Vec<Value>),
+    Sites(String, Vec<Value>),
+    Install(Value, Vec<Value>),
+    Done(String),
+    Failed(String)
+}
+
+/// Answer requests one at a time until the screen closes its end of the channel.
+fn serve(api: &API, requests: Receiver<Request>, replies: Sender<Reply>) {
+    for request in requests {
+        let reply = match request {
+            // A dry run prints the request, which would be drawn over the screen, so only the status line says so.
+            Request::PurgeCache(_, kind) if api.is_dry_run() => Ok(Reply::Done(format!("Dry run, the {} cache purge wasn't sent.", kind))),
+            Request::Backup(_, name) if api.is_dry_run() => Ok(Reply::Done(format!("Dry run, the backup of {} wasn't requested.", name))),
+            Request::Accounts => api.get_all_accounts().map(Reply::Accounts),
+            Request::Sites(account) => api
+                .get_all_sites(Some(&account))
+                .map(|sites| Reply::Sites(account, sites)),
+            Request::Install(id) => api
+                .get_install_by_id(&id)
+                .and_then(|install| Ok(Reply::Install(install, api.get_all_domains(&id)?))),
+            Request::PurgeCache(id, kind) => api
+                .purge_cache(&id, kind.clone())
+                .map(|_| Reply::Done(format!("Purged {} cache.", kind))),
+            Request::Backup(id, name) => {
+                let data = Backup {
+                    description: "Requested from wpe tui".to_string(),
+                    notification_emails: Vec::new()
+                };
+                api.backup(&id, &data).map(|_| Reply::Done(format!("Backup of {} requested.", name)))
+            }
+        };
+
+        if replies.send(reply.unwrap_or_else(|e| Reply::Failed(e.to_string()))).is_err() {
+            break;
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Accounts,
+    Sites,
+    Installs
+}
+
+/// A key that needs a follow-up answer before anything is sent.
+enum Prompt {
+    Purge,
+    Backup
+}
+
+struct App<'a> {
+    requests: Sender<Request>,
+    account: Option<&'a str>,
+    focus: Pane,
+    accounts: Vec<Value>,
+    sites: Vec<Value>,
+    install: Option<Value>,
+    domains: Vec<Value>,
+    account_state: ListState,
+    site_state: ListState,
+    install_state: ListState,
+    prompt: Option<Prompt>,
+    status: String,
+    pending: usize,
+    refreshed: Instant
+}
+
+fn id_of(item: &Value) -> Option<String> {
+    item["id"].as_str().map(String::from)
+}
+
+/// Keep the same item selected after a reload, or fall back to the first.
+fn reselect(state: &mut ListState, items: &[Value], id: Option<String>) {
+    let index = id
+        .and_then(|id| items.iter().position(|i| i["id"] == id.as_str()))
+        .or(if items.is_empty() { None } else { Some(0) });
+    state.select(index);
+}
+
+fn selected<'v>(state: &ListState, items: &'v [Value]) -> Option<&'v Value> {
+    state.selected().and_then(|i| items.get(i))
+}
+
+impl<'a> App<'a> {
+    fn new(requests: Sender<Request>, account: Option<&'a str>) -> Self {
+        Self {
+            requests,
+            account,
+            focus: Pane::Accounts,
+            accounts: Vec::new(),
+            sites: Vec::new(),
+            install: None,
+            domains: Vec::new(),
+            account_state: ListState::default(),
+            site_state: ListState::default(),
+            install_state: ListState::default(),
+            prompt: None,
+            status: String::new(),
+            pending: 0,
+            refreshed: Instant::now()
+        }
+    }
+
+    fn send(&mut self, request: Request) {
+        if self.requests.send(request).is_ok() {
+            self.pending += 1;
+        }
+    }
+
+    /// The installs listed on the selected site.
+    fn installs(&self) -> Vec<Value> {
+        selected(&self.site_state, &self.sites)
+            .and_then(|s| s["installs"].as_array().cloned())
+            .unwrap_or_default()
+    }
+
+    fn account_id(&self) -> Option<String> {
+        selected(&self.account_state, &self.accounts).and_then(id_of)
+    }
+
+    fn site_id(&self) -> Option<String> {
+        selected(&self.site_state, &self.sites).and_then(id_of)
+    }
+
+    fn install_id(&self) -> Option<String> {
+        selected(&self.install_state, &self.installs()).and_then(id_of)
+    }
+
+    /// Reload everything currently on screen, unless earlier requests are still out.
+    /// A slow API would otherwise build up a backlog of reloads.
+    fn refresh(&mut self) -> bool {
+        if self.pending > 0 {
+            return false;
+        }
+        self.refreshed = Instant::now();
+        self.send(Request::Accounts);
+        if let Some(id) = self.account_id() {
+            self.send(Request::Sites(id));
+        }
+        if let Some(id) = self.install_id() {
+            self.send(Request::Install(id));
+        }
+        true
+    }
+
+    fn account_changed(&mut self) {
+        self.sites.clear();
+        self.site_state.select(None);
+        self.install_changed();
+        if let Some(id) = self.account_id() {
+            self.send(Request::Sites(id));
+        }
+    }
+
+    fn site_changed(&mut self) {
+        let installs = self.installs();
+        reselect(&mut self.install_state, &installs, None);
+        self.install_changed();
+    }
+
+    fn install_changed(&mut self) {
+        self.install = None;
+        self.domains.clear();
+        if let Some(id) = self.install_id() {
+            self.send(Request::Install(id));
+        }
+    }
+
+    fn apply(&mut self, reply: Reply) {
+        self.pending = self.pending.saturating_sub(1);
+
+        match reply {
+            Reply::Accounts(accounts) => {
+                let previous = self.account_id();
+                self.accounts = accounts
+                    .into_iter()
+                    .filter(|a| self.account.is_none_or(|id| a["id"] == id))
+                    .collect();
+                reselect(&mut self.account_state, &self.accounts, previous.clone());
+                if self.account_id() != previous {
+                    self.account_changed();
+                }
+            },
+            Reply::Sites(account, sites) => {
+                // Ignore listings for an account that is no longer selected.
+                if self.account_id().as_deref() != Some(account.as_str()) {
+                    return;
+                }
+                let (site, install) = (self.site_id(), self.install_id());
+                self.sites = sites;
+                reselect(&mut self.site_state, &self.sites, site);
+                let installs = self.installs();
+                reselect(&mut self.install_state, &installs, install.clone());
+                if self.install_id() != install {
+                    self.install_changed();
+                }
+            },
+            Reply::Install(install, domains) => {
+                if id_of(&install) == self.install_id() {
+                    self.install = Some(install);
+                    self.domains = domains;
+                }
+            },
+            Reply::Done(message) => self.status = message,
+            Reply::Failed(message) => self.status = format!("Error: {}", message)
+        }
+    }
+
+    fn move_selection(&mut self, down: bool) {
+        let len = match self.focus {
+            Pane::Accounts => self.accounts.len(),
+            Pane::Sites => self.sites.len(),
+            Pane::Installs => self.installs().len()
+        };
+        let state = match self.focus {
+            Pane::Accounts => &mut self.account_state,
+            Pane::Sites => &mut self.site_state,
+            Pane::Installs => &mut self.install_state
+        };
+        let Some(current) = state.selected() else { return };
+        let next = if down {
+            (current + 1).min(len.saturating_sub(1))
+        } else {
+            current.saturating_sub(1)
+        };
+        if next == current {
+            return;
+        }
+        state.select(Some(next));
+
+        match self.focus {
+            Pane::Accounts => self.account_changed(),
+            Pane::Sites => self.site_changed(),
+            Pane::Installs => self.install_changed()
+        }
+    }
+
+    /// Handle a key press. Returns `false` once the dashboard should close.
+    fn handle(&mut self, key: KeyEvent) -> bool {
+        if let Some(prompt) = self.prompt.take() {
+            self.answer(prompt, key.code);
+            return true;
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Tab | KeyCode::Right | KeyCode::Char('l') => {
+                self.focus = match self.focus {
+                    Pane::Accounts => Pane::Sites,
+                    Pane::Sites => Pane::Installs,
+                    Pane::Installs => Pane::Accounts
+                };
+            },
+            KeyCode::BackTab | KeyCode::Left | KeyCode::Char('h') => {
+                self.focus = match self.focus {
+                    Pane::Accounts => Pane::Installs,
+                    Pane::Sites => Pane::Accounts,
+                    Pane::Installs => Pane::Sites
+                };
+            },
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(true),
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(false),
+            KeyCode::Char('r') => {
+                self.status = if self.refresh() { "Refreshing…" } else { "Still loading, try again in a moment." }.to_string();
+            },
+            KeyCode::Char(c) => self.act(c),
+            _ => {}
+        }
+
+        true
+    }
+
+    /// Actions on the selected install.
+    fn act(&mut self, key: char) {
+        if !matches!(key, 'p' | 'b' | 'o' | 's') {
+            return;
+        }
+        let Some(install) = self.install.clone() else {
+            self.status = "Select an install first.".to_string();
+            return;
+        };
+        let name = install["name"].as_str().unwrap_or_default();
+
+        match key {
+            'p' => {
+                self.status = "Purge which cache? [o]bject [p]age [c]dn".to_string();
+                self.prompt = Some(Prompt::Purge);
+            },
+            'b' => {
+                self.status = format!("Back up {}? [y/n]", name);
+                self.prompt = Some(Prompt::Backup);
+            },
+            'o' => {
+                let domain = install["primary_domain"]
+                    .as_str()
+                    .or(install["cname"].as_str())
+                    .unwrap_or_default();
+                let url = format!("https://{}", domain);
+                self.status = match open_url(&url) {
+                    Ok(_) => format!("Opened {}", url),
+                    Err(e) => format!("Error: {}", e)
+                };
+            },
+            _ => {
+                let host = format!("{0}@{0}.ssh.wpengine.net", name);
+                self.status = match copy(&host) {
+                    Ok(_) => format!("Copied {}", host),
+                    Err(e) => format!("Error: {}", e)
+                };
+            }
+        }
+    }
+
+    fn answer(&mut self, prompt: Prompt, code: KeyCode) {
+        let (Some(id), Some(install)) = (self.install_id(), self.install.as_ref()) else { return };
+        let name = install["name"].as_str().unwrap_or_default().to_string();
+
+        match (prompt, code) {
+            (Prompt::Purge, KeyCode::Char(c @ ('o' | 'p' | 'c'))) => {
+                let kind = match c {
+                    'o' => "object",
+                    'p' => "page",
+                    _ => "cdn"
+                };
+                self.status = format!("Purging {} cache of {}…", kind, name);
+                self.send(Request::PurgeCache(id, kind.to_string()));
+            },
+            (Prompt::Backup, KeyCode::Char('y')) => {
+                self.status = format!("Requesting a backup of {}…", name);
+                self.send(Request::Backup(id, name));
+            },
+            _ => self.status = "Cancelled.".to_string()
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [top, bottom, footer] = Layout::vertical([
+            Constraint::Percentage(50),
+            Constraint::Fill(1),
+            Constraint::Length(1)
+        ]).areas(frame.area());
+        let [accounts, sites, installs] = Layout::horizontal([Constraint::Ratio(1, 3); 3]).areas(top);
+        let [details, domains] = Layout::horizontal([
+            Constraint::Percentage(60),
+            Constraint::Percentage(40)
+        ]).areas(bottom);
+
+        let labels = |items: &[Value]| -> Vec<ListItem<'static>> {
+            items
+                .iter()
+                .map(|i| ListItem::new(i["name"].as_str().unwrap_or_default().to_string()))
+                .collect()
+        };
+        let install_labels: Vec<ListItem> = self
+            .installs()
+            .iter()
+            .map(|i| ListItem::new(format!(
+                "{} ({})",
+                i["name"].as_str().unwrap_or_default(),
+                i["environment"].as_str().unwrap_or_default()
+            )))
+            .collect();
+
+        let account_list = self.list("Accounts", Pane::Accounts, labels(&self.accounts));
+        frame.render_stateful_widget(account_list, accounts, &mut self.account_state);
+        let site_list = self.list("Sites", Pane::Sites, labels(&self.sites));
+        frame.render_stateful_widget(site_list, sites, &mut self.site_state);
+        let installs_list = self.list("Installs", Pane::Installs, install_labels);
+        frame.render_stateful_widget(installs_list, installs, &mut self.install_state);
+
+        self.draw_details(frame, details);
+
+        let domain_items: Vec<ListItem> = self.domains
+            .iter()
+            .map(|d| {
+                let name = d["name"].as_str().unwrap_or_default();
+                if d["primary"].as_bool().unwrap_or(false) {
+                    ListItem::new(format!("{} (primary)", name))
+                } else {
+                    ListItem::new(name.to_string())
+                }
+            })
+            .collect();
+        frame.render_widget(List::new(domain_items).block(Block::bordered().title("Domains")), domains);
+
+        let spinner = if self.pending > 0 { "⟳ " } else { "" };
+        let text = if self.status.is_empty() {
+            format!("{}{}", spinner, HELP)
+        } else {
+            format!("{}{} · {}", spinner, self.status, HELP)
+        };
+        frame.render_widget(Paragraph::new(text).style(Style::default().fg(Color::DarkGray)), footer);
+    }
+
+    fn list(&self, title: &'static str, pane: Pane, items: Vec<ListItem<'static>>) -> List<'static> {
+        let border = if self.focus == pane {
+            Style::default().fg(Color::Cyan)
+        } else {
+            Style::default()
+        };
+
+        List::new(items)
+            .block(Block::bordered().title(title).border_style(border))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("› ")
+    }
+
+    fn draw_details(&self, frame: &mut Frame, area: Rect) {
+        let lines: Vec<Line> = match &self.install {
+            Some(Value::Object(map)) => map
+                .iter()
+                .map(|(key, value)| {
+                    let text = match value {
+                        Value::String(s) => s.to_string(),
+                        Value::Null => String::new(),
+                        // References like `account` read better as their ID.
+                        Value::Object(o) => o.get("id").and_then(Value::as_str).map(String::from).unwrap_or_else(|| value.to_string()),
+                        v => v.to_string()
+                    };
+                    Line::from(format!("{}: {}", key, text))
+                })
+                .collect(),
+            _ if self.install_id().is_some() => vec![Line::from("Loading…")],
+            _ => vec![Line::from("No install selected.")]
+        };
+
+        frame.render_widget(
+            Paragraph::new(lines)
+                .block(Block::bordered().title("Details"))
+                .wrap(Wrap { trim: false }),
+            area
+        );
+    }
+}
+
+fn open_url(url: &str) -> Result<()> {
+    #[cfg(target_os = "macos")]
+    let mut command = std::process::Command::new("open");
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    };
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let mut command = std::process::Command::new("xdg-open");
+
+    command
+        .arg(url)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+/// Copy to the clipboard with an OSC 52 escape, which also works over SSH.
+fn copy(text: &str) -> Result<()> {
+    let mut stdout = std::io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", STANDARD.encode(text))?;
+    stdout.flush()?;
+    Ok(())
+}
+
+fn run(terminal: &mut DefaultTerminal, app: &mut App, replies: &Receiver<Reply>, interval: Duration) -> Result<()> {
+    loop {
+        while let Ok(reply) = replies.try_recv() {
+            app.apply(reply);
+        }
+        if app.refreshed.elapsed() >= interval {
+            // Skipped while loading, it is tried again on the next tick.
+            app.refresh();
+        }
+
+        terminal.draw(|frame| app.draw(frame))?;
+
+        if event::poll(Duration::from_millis(200))? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !app.handle(key) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Provides logic for the tui command.
+///
+/// # Arguments
+///
+/// * `interval` - u64
+/// * `api` - &API
+/// * `account` - Option<&str>
+pub fn init(interval: u64, api: &API, account: Option<&str>) -> Result<()> {
+    if !std::io::stdout().is_terminal() {
+        return Err(CliError::new(ErrorKind::Usage, "wpe tui needs an interactive terminal.").into());
+    }
+    let interval = Duration::from_secs(interval);
+
+    let (request_tx, request_rx) = mpsc::channel();
+    let (reply_tx, reply_rx) = mpsc::channel();
+
+    thread::scope(|scope| {
+        scope.spawn(move || serve(api, request_rx, reply_tx));
+
+        let mut app = App::new(request_tx, account);
+        app.refresh();
+
+        let mut terminal = ratatui::init();
+        let result = run(&mut terminal, &mut app, &reply_rx, interval);
+        ratatui::restore();
+
+        // Closing the request channel lets the background thread finish.
+        drop(app);
+        result
+    })
+}
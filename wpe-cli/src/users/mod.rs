@@ -0,0 +1,125 @@
+use anyhow::Result;
+use wpe::*;
+use serde_json::Value;
+use crate::output::{Output, Kind};
+use crate::cli::{UsersArgs, UserCommand};
+use crate::accounts;
+use crate::guard::{self, Guard, Impact};
+use crate::diff;
+use crate::input::{Bodies, Update};
+
+/// Find a user of an account by ID or email.
+pub fn resolve_user(api: &API, account_id: &str, input: &str) -> Result<String> {
+    if is_uuid(input) {
+        return Ok(input.to_string());
+    }
+
+    api.get_all_account_users(account_id)?
+        .iter()
+        .find(|u| u["email"].as_str().is_some_and(|e| e.eq_ignore_ascii_case(input)))
+        .and_then(|u| u["user_id"].as_str().map(String::from))
+        .ok_or_else(|| CliError::new(ErrorKind::NotFound, format!("No user with email '{}' was found.", input)).into())
+}
+
+/// Resolve each install name to an ID.
+pub fn resolve_installs(api: &API, installs: &[String]) -> Result<Vec<String>> {
+    installs.iter().map(|i| api.resolve(Resource::Install, i)).collect()
+}
+
+/// Update a user once its diff is reviewed. Returns null when nothing was sent.
+fn update(api: &API, account_id: &str, id: &str, data: &AccountUserPatch, headless: bool) -> Result<Value> {
+    let current = api.get_user_by_id(account_id, id)?;
+    let what = format!("user '{}'", current["email"].as_str().unwrap_or(id));
+    if !diff::review(&what, &diff::changes(&current, &data.user, diff::USER_FIELDS)?, headless)? {
+        return Ok(Value::Null);
+    }
+
+    api.update_user(account_id, id, data)
+}
+
+/// Provides logic for the users command.
+///
+/// # Arguments
+///
+/// * `args` - &UsersArgs
+/// * `api` - &API
+/// * `headless` - bool
+/// * `out` - &Output
+/// * `account` - Option<&str>
+pub fn init(args: &UsersArgs, api: &API, headless: bool, out: &Output, account: Option<&str>) -> Result<()> {
+    let account_id = &accounts::require(api, account, headless, "manage users")?;
+
+    match &args.command {
+        UserCommand::List { page } => {
+            let users = api.get_account_users(account_id, Some(*page))?;
+            out.print(&users["results"], Kind::Users)?;
+        },
+        UserCommand::Get { user } => {
+            let user_id = resolve_user(api, account_id, user)?;
+            let user = api.get_user_by_id(account_id, &user_id)?;
+            out.print(&user, Kind::Users)?;
+        },
+        UserCommand::Add { email, first_name, last_name, roles, installs, body } => {
+            let bodies = Bodies::from(&body.file, || Ok(AccountUser {
+                user: User {
+                    account_id: String::new(),
+                    first_name: first_name.clone().unwrap_or_default(),
+                    last_name: last_name.clone().unwrap_or_default(),
+                    email: email.clone().unwrap_or_default(),
+                    roles: roles.clone().unwrap_or_default(),
+                    install_ids: installs.clone()
+                }
+            }))?;
+
+            let mut add_user = Vec::new();
+            for data in &bodies.items {
+                let mut user = data.user.clone();
+                if user.account_id.is_empty() {
+                    user.account_id = account_id.to_string();
+                }
+                user.install_ids = resolve_installs(api, &user.install_ids)?;
+
+                add_user.push(api.add_user(&user.account_id.clone(), &AccountUser { user })?);
+            }
+
+            out.print(&bodies.output(add_user), Kind::Users)?;
+        },
+        UserCommand::Update { user, roles, installs, body } => {
+            let bodies = Bodies::from(&body.file, || {
+                if roles.is_none() && installs.is_empty() {
+                    return Err(CliError::new(ErrorKind::Usage, "Nothing to update, pass --roles or --install.").into());
+                }
+                Ok(Update::new(AccountUserPatch {
+                    user: UserPatch {
+                        roles: roles.clone(),
+                        install_ids: (!installs.is_empty()).then(|| installs.clone())
+                    }
+                }))
+            })?;
+
+            let mut update_user = Vec::new();
+            for body in &bodies.items {
+                let user_id = resolve_user(api, account_id, body.target(user.as_deref())?)?;
+                let data = AccountUserPatch {
+                    user: UserPatch {
+                        roles: body.patch.user.roles.clone(),
+                        install_ids: body.patch.user.install_ids.as_ref().map(|i| resolve_installs(api, i)).transpose()?
+                    }
+                };
+                update_user.push(update(api, account_id, &user_id, &data, headless)?);
+            }
+
+            out.print(&bodies.output(update_user), Kind::Users)?;
+        },
+        UserCommand::Delete { user, yes } => {
+            let user_id = resolve_user(api, account_id, user)?;
+            let email = api.get_user_by_id(account_id, &user_id)?["email"].as_str().unwrap_or(&user_id).to_string();
+            let guard = Guard { yes: *yes, force_production: false, headless };
+            guard::confirm(&Impact::new("user", &email), &guard)?;
+
+            api.delete_user(account_id, &user_id)?;
+        }
+    }
+
+    Ok(())
+}